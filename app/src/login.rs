@@ -3,6 +3,7 @@ use crate::storage::vector_storage::{EmbeddingStorage, EmbeddingRecord};
 use candle_core::Tensor;
 use anyhow::Result;
 use candle_core::Device;
+use ex03_similarity_solution::verification::is_match;
 use crate::camera::camera_interactions::{capture_and_compute_average_embedding};
 
 pub fn login(model: &Func, storage: &dyn EmbeddingStorage, user_name: &str) -> Result<bool> {
@@ -39,7 +40,7 @@ pub fn login(model: &Func, storage: &dyn EmbeddingStorage, user_name: &str) -> R
 
     let login_threshold = 0.7; 
 
-    if best_match_similarity > login_threshold {
+    if is_match(best_match_similarity, login_threshold) {
         println!("[+] Login successful for user '{user_name}' with similarity: {best_match_similarity:.4}");
         Ok(true)
     } else {
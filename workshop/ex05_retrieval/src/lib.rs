@@ -13,6 +13,7 @@ pub fn add_record(storage: &mut dyn EmbeddingStorage, name: &str, embedding: Vec
         embedding,
         created_at: chrono::Utc::now(),
         metadata: HashMap::new(),
+        expires_at: None,
     };
     
     let id = record.id.clone();
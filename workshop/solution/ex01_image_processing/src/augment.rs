@@ -0,0 +1,94 @@
+use anyhow::Result;
+use candle_core::{DType, Tensor};
+
+use crate::imagenet::IMAGENET_STD;
+
+/// Brightness change applied by the lighter / darker variants, in [0, 1] pixel units.
+const BRIGHTNESS_SHIFT: f32 = 0.08;
+/// Rotation applied by the two rotated variants, in degrees either way.
+const ROTATION_DEGREES: f32 = 5.0;
+
+/// Mild variations of an ImageNet-normalized (3, H, W) tensor, for averaging into a sturdier
+/// enrollment template: the original (always first), a horizontal mirror, a lighter and a darker
+/// copy, and the image rotated a few degrees each way. Every variant keeps the input shape.
+pub fn variants(img: &Tensor) -> Result<Vec<Tensor>> {
+    let (channels, _, _) = img.dims3()?;
+    if channels != 3 {
+        anyhow::bail!("Expected a (3, H, W) image tensor, got {} channels", channels);
+    }
+    Ok(vec![
+        img.clone(),
+        flip_horizontal(img)?,
+        shift_brightness(img, BRIGHTNESS_SHIFT)?,
+        shift_brightness(img, -BRIGHTNESS_SHIFT)?,
+        rotate(img, ROTATION_DEGREES)?,
+        rotate(img, -ROTATION_DEGREES)?,
+    ])
+}
+
+/// Mirror the image left to right.
+pub fn flip_horizontal(img: &Tensor) -> Result<Tensor> {
+    let width = img.dim(2)?;
+    let reversed: Vec<u32> = (0..width as u32).rev().collect();
+    let indices = Tensor::new(reversed.as_slice(), img.device())?;
+    Ok(img.index_select(&indices, 2)?)
+}
+
+/// Add `delta` (in [0, 1] pixel units) to every channel, expressed in normalized units.
+fn shift_brightness(img: &Tensor, delta: f32) -> Result<Tensor> {
+    let per_channel: Vec<f32> = IMAGENET_STD.iter().map(|std| delta / std).collect();
+    let offsets = Tensor::from_vec(per_channel, (3, 1, 1), img.device())?.to_dtype(img.dtype())?;
+    Ok(img.broadcast_add(&offsets)?)
+}
+
+/// Rotate counter-clockwise by `degrees` about the centre with bilinear sampling.
+/// Pixels that would come from outside the frame repeat the nearest edge pixel.
+fn rotate(img: &Tensor, degrees: f32) -> Result<Tensor> {
+    let (_, height, width) = img.dims3()?;
+    let planes = img.to_dtype(DType::F32)?.to_vec3::<f32>()?;
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+    let clamp = |v: f32, max: usize| v.clamp(0.0, (max - 1) as f32);
+
+    let mut out = Vec::with_capacity(3 * height * width);
+    for plane in &planes {
+        for y in 0..height {
+            for x in 0..width {
+                // Inverse mapping: where in the source does this output pixel come from
+                let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+                let sx = clamp(cos * dx - sin * dy + cx, width);
+                let sy = clamp(sin * dx + cos * dy + cy, height);
+                let (x0, y0) = (sx.floor() as usize, sy.floor() as usize);
+                let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+                let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+                let top = plane[y0][x0] * (1.0 - fx) + plane[y0][x1] * fx;
+                let bottom = plane[y1][x0] * (1.0 - fx) + plane[y1][x1] * fx;
+                out.push(top * (1.0 - fy) + bottom * fy);
+            }
+        }
+    }
+    Ok(Tensor::from_vec(out, (3, height, width), img.device())?.to_dtype(img.dtype())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imagenet::load_image224;
+
+    #[test]
+    fn variants_start_with_the_original_and_keep_its_shape() -> Result<()> {
+        let original = load_image224("../../../app/test_images/brad1.png")?;
+        let augmented = variants(&original)?;
+        assert_eq!(augmented.len(), 6);
+        assert_eq!(augmented[0].to_vec3::<f32>()?, original.to_vec3::<f32>()?);
+        for variant in &augmented[1..] {
+            assert_eq!(variant.dims(), original.dims());
+            assert_ne!(variant.to_vec3::<f32>()?, original.to_vec3::<f32>()?);
+        }
+
+        // Mirroring twice is the identity
+        assert_eq!(flip_horizontal(&augmented[1])?.to_vec3::<f32>()?, original.to_vec3::<f32>()?);
+        assert!(variants(&original.narrow(0, 0, 1)?).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use candle_core::Tensor;
+use face_auth_error::FaceAuthError;
+use image::DynamicImage;
+use rustface::ImageData;
+
+use crate::image_with_std_mean;
+use crate::imagenet::{open_image, IMAGENET_MEAN, IMAGENET_STD};
+
+/// SeetaFace frontal face model shipped with the `rustface` crate (BSD 2-Clause).
+const SEETA_MODEL: &[u8] = include_bytes!("../models/seeta_fd_frontal_v1.0.bin");
+
+/// Images are scaled down so their longest side is at most this many pixels before detection.
+const DETECTION_MAX_SIDE: u32 = 640;
+
+#[derive(Debug, Clone)]
+pub struct DetectOptions {
+    /// Minimum detector score for a window to count as a face (SeetaFace uses 2.0 by default).
+    pub score_threshold: f64,
+    /// Smallest face size in pixels of the downscaled detection image (at least 20).
+    pub min_face_size: u32,
+    /// Only return the face with the largest bounding box.
+    pub largest_only: bool,
+}
+
+impl Default for DetectOptions {
+    fn default() -> Self {
+        DetectOptions {
+            score_threshold: 2.0,
+            min_face_size: 20,
+            largest_only: false,
+        }
+    }
+}
+
+/// A detected face, in pixel coordinates of the original image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub score: f64,
+}
+
+impl FaceBox {
+    pub fn area(&self) -> u32 {
+        self.width * self.height
+    }
+}
+
+/// Detect faces in `img_path` and return one ImageNet-normalized (3, 224, 224) tensor per face.
+/// Returns an empty Vec when no face is found.
+pub fn detect_and_crop(img_path: &str) -> Result<Vec<Tensor>, FaceAuthError> {
+    detect_and_crop_with(img_path, &DetectOptions::default())
+}
+
+/// The image is oriented by its EXIF tag before detection; unreadable files yield `ImageLoad`.
+pub fn detect_and_crop_with(img_path: &str, options: &DetectOptions) -> Result<Vec<Tensor>, FaceAuthError> {
+    let img = open_image(img_path)?;
+    let crops = detect_faces(&img, options)?
+        .iter()
+        .map(|face| {
+            let crop = img.crop_imm(face.x, face.y, face.width, face.height);
+            image_with_std_mean(&crop, 224, &IMAGENET_MEAN, &IMAGENET_STD)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(crops)
+}
+
+/// Run the face detector on `img`, most confident face first.
+pub fn detect_faces(img: &DynamicImage, options: &DetectOptions) -> Result<Vec<FaceBox>> {
+    let model = rustface::read_model(SEETA_MODEL)?;
+    let mut detector = rustface::create_detector_with_model(model);
+    detector.set_min_face_size(options.min_face_size.max(20));
+    detector.set_score_thresh(options.score_threshold);
+    detector.set_pyramid_scale_factor(0.8);
+    detector.set_slide_window_step(4, 4);
+
+    let longest = img.width().max(img.height());
+    let scale = if longest > DETECTION_MAX_SIDE {
+        DETECTION_MAX_SIDE as f32 / longest as f32
+    } else {
+        1.0
+    };
+    let gray = img
+        .resize(
+            (img.width() as f32 * scale) as u32,
+            (img.height() as f32 * scale) as u32,
+            image::imageops::FilterType::Triangle,
+        )
+        .to_luma8();
+    let (width, height) = gray.dimensions();
+    let faces = detector.detect(&ImageData::new(&gray, width, height));
+
+    let mut boxes: Vec<FaceBox> = faces
+        .iter()
+        .filter_map(|face| {
+            // Map back to the original resolution and clamp to the image bounds
+            let bbox = face.bbox();
+            let x = (bbox.x().max(0) as f32 / scale) as u32;
+            let y = (bbox.y().max(0) as f32 / scale) as u32;
+            let right = ((bbox.x() + bbox.width() as i32).max(0) as f32 / scale) as u32;
+            let bottom = ((bbox.y() + bbox.height() as i32).max(0) as f32 / scale) as u32;
+            let right = right.min(img.width());
+            let bottom = bottom.min(img.height());
+            (right > x && bottom > y).then(|| FaceBox {
+                x,
+                y,
+                width: right - x,
+                height: bottom - y,
+                score: face.score(),
+            })
+        })
+        .collect();
+    boxes.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+
+    if options.largest_only {
+        if let Some(largest) = boxes.iter().max_by_key(|face| face.area()).cloned() {
+            boxes = vec![largest];
+        }
+    }
+    Ok(boxes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_faces_in_fixtures() -> Result<()> {
+        for path in ["brad1.png", "brad2.png", "tom.png"] {
+            let faces = detect_and_crop(&format!("../../../app/test_images/{path}"))?;
+            assert!(!faces.is_empty(), "No face detected in {}", path);
+            assert_eq!(faces[0].dims(), &[3, 224, 224]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn largest_only_and_threshold() -> Result<()> {
+        let path = "../../../app/test_images/brad1.png";
+        let options = DetectOptions { largest_only: true, ..DetectOptions::default() };
+        assert_eq!(detect_and_crop_with(path, &options)?.len(), 1);
+
+        // No window can reach an absurd confidence, so nothing is returned
+        let options = DetectOptions { score_threshold: 1e9, ..DetectOptions::default() };
+        assert!(detect_and_crop_with(path, &options)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn blank_image_has_no_faces() -> Result<()> {
+        let img = DynamicImage::new_rgb8(200, 200);
+        assert!(detect_faces(&img, &DetectOptions::default())?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,359 @@
+use anyhow::Context;
+use candle_core::{Device, Tensor};
+use face_auth_error::FaceAuthError;
+use image::{DynamicImage, ImageDecoder, ImageFormat};
+use std::io::{BufRead, Cursor, Seek};
+
+use crate::{image_with_std_mean, image_with_std_mean_cropped, CropMode};
+
+pub const IMAGENET_MEAN: [f32; 3] = [0.485f32, 0.456, 0.406];
+pub const IMAGENET_STD: [f32; 3] = [0.229f32, 0.224, 0.225];
+
+/// Per-channel (R, G, B) constants a backbone was trained with: `(pixel / 255 - mean) / std`.
+/// Defaults to the ImageNet values used by `load_image224`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Normalization {
+            mean: IMAGENET_MEAN,
+            std: IMAGENET_STD,
+        }
+    }
+}
+
+/// Load an image from disk into an ImageNet-normalized (3, 224, 224) tensor.
+/// The EXIF orientation tag, when present, is applied first so phone photos come out upright.
+pub fn load_image224(path: &str) -> Result<Tensor, FaceAuthError> {
+    load_image(path, 224)
+}
+
+/// Load several images into one (N, 3, 224, 224) batch on the CPU, in the order given.
+pub fn load_image224_batch(paths: &[&str]) -> Result<Tensor, FaceAuthError> {
+    load_image224_batch_on(paths, &Device::Cpu)
+}
+
+/// Like `load_image224_batch`, placing the batch on `device`.
+/// A file that fails to load is reported as `ImageLoad` naming its path; an empty list is an error.
+pub fn load_image224_batch_on(paths: &[&str], device: &Device) -> Result<Tensor, FaceAuthError> {
+    if paths.is_empty() {
+        return Err(anyhow::anyhow!("Cannot build an image batch from no paths").into());
+    }
+    let images = paths.iter().map(|path| load_image224(path)).collect::<Result<Vec<_>, _>>()?;
+    let batch = Tensor::stack(&images, 0).map_err(anyhow::Error::from)?;
+    Ok(batch.to_device(device).map_err(anyhow::Error::from)?)
+}
+
+/// Like `load_image224`, choosing how non-square photos are fitted to 224x224.
+pub fn load_image224_with(path: &str, mode: CropMode) -> Result<Tensor, FaceAuthError> {
+    let img = open_image(path)?;
+    Ok(image_with_std_mean_cropped(&img, 224, &IMAGENET_MEAN, &IMAGENET_STD, mode)?)
+}
+
+/// Like `load_image224`, normalizing with `norm` instead of the ImageNet constants.
+pub fn load_image224_normalized(path: &str, norm: Normalization) -> Result<Tensor, FaceAuthError> {
+    load_image_with_std_mean(path, 224, &norm.mean, &norm.std)
+}
+
+/// Load an image from disk into an ImageNet-normalized (3, size, size) tensor.
+pub fn load_image(path: &str, size: usize) -> Result<Tensor, FaceAuthError> {
+    load_image_with_std_mean(path, size, &IMAGENET_MEAN, &IMAGENET_STD)
+}
+
+/// Load an image from disk into a (3, size, size) tensor normalized with a custom mean/std.
+pub fn load_image_with_std_mean(
+    path: &str,
+    size: usize,
+    mean: &[f32; 3],
+    std: &[f32; 3],
+) -> Result<Tensor, FaceAuthError> {
+    let img = open_image(path)?;
+    Ok(image_with_std_mean(&img, size, mean, std)?)
+}
+
+/// Same as `load_image224`, but decodes from an in-memory buffer (e.g. an upload).
+/// The format (PNG, JPEG, ...) is detected from the bytes themselves.
+/// Decode failures are reported as `ImageLoad` with the path `<memory>`.
+pub fn load_image224_from_bytes(bytes: &[u8]) -> Result<Tensor, FaceAuthError> {
+    let img = decode_bytes(bytes).map_err(|source| FaceAuthError::image_load("<memory>", source))?;
+    Ok(image_with_std_mean(&img, 224, &IMAGENET_MEAN, &IMAGENET_STD)?)
+}
+
+/// Open and decode an image file, upright according to its EXIF orientation.
+/// Unreadable, empty, truncated and non-image files all fail with `ImageLoad`.
+pub(crate) fn open_image(path: &str) -> Result<DynamicImage, FaceAuthError> {
+    let decode = || -> anyhow::Result<DynamicImage> {
+        let bytes = std::fs::read(path).context("Failed to read image file")?;
+        decode_bytes(&bytes)
+    };
+    decode().map_err(|source| FaceAuthError::image_load(path, source))
+}
+
+/// Detect the format from the bytes and decode them, refusing JPEGs cut off before their end.
+fn decode_bytes(bytes: &[u8]) -> anyhow::Result<DynamicImage> {
+    let reader = image::ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    // The JPEG decoder pads a truncated scan with grey instead of failing, so check for the
+    // end-of-image marker ourselves. Whatever follows it is ignored.
+    if reader.format() == Some(ImageFormat::Jpeg) && !has_jpeg_end_marker(bytes) {
+        anyhow::bail!("Truncated JPEG: end-of-image marker not found");
+    }
+    decode_oriented(reader)
+}
+
+/// Walk the JPEG marker segments looking for the end-of-image marker (FF D9). Segments are
+/// skipped by their length, so thumbnails embedded in EXIF data do not count; after each
+/// start-of-scan the entropy-coded data runs until the next marker, where `FF 00` is an
+/// escaped data byte and `FF D0`..`FF D7` are restart markers inside the scan.
+fn has_jpeg_end_marker(bytes: &[u8]) -> bool {
+    let mut pos = 2; // past the start-of-image marker
+    loop {
+        // Markers may be preceded by any number of FF fill bytes
+        while bytes.get(pos) == Some(&0xFF) && bytes.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        let (Some(0xFF), Some(&marker)) = (bytes.get(pos), bytes.get(pos + 1)) else {
+            return false;
+        };
+        pos += 2;
+        match marker {
+            0xD9 => return true,
+            0x01 | 0xD0..=0xD7 => continue,
+            _ => {}
+        }
+        let Some(length) = bytes.get(pos..pos + 2) else {
+            return false;
+        };
+        pos += usize::from(u16::from_be_bytes([length[0], length[1]]));
+        if marker == 0xDA {
+            loop {
+                match (bytes.get(pos), bytes.get(pos + 1)) {
+                    (Some(0xFF), Some(0x00 | 0xD0..=0xD7)) => pos += 2,
+                    (Some(0xFF), Some(_)) => break,
+                    (Some(_), _) => pos += 1,
+                    (None, _) => return false,
+                }
+            }
+        }
+    }
+}
+
+/// Decode an image and rotate/flip it according to its EXIF orientation.
+fn decode_oriented<R: BufRead + Seek>(reader: image::ImageReader<R>) -> anyhow::Result<DynamicImage> {
+    if reader.format().is_none() {
+        anyhow::bail!("Unsupported or unrecognized image format");
+    }
+    let mut decoder = reader.into_decoder().context("Failed to read image header")?;
+    let orientation = decoder.orientation()?;
+    let mut img = DynamicImage::from_decoder(decoder).context("Failed to decode image data")?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn batch_stacks_fixtures_in_order() -> Result<()> {
+        let paths = [
+            "../../../app/test_images/brad1.png",
+            "../../../app/test_images/brad2.png",
+            "../../../app/test_images/tom.png",
+        ];
+        let batch = load_image224_batch(&paths)?;
+        assert_eq!(batch.dims(), &[3, 3, 224, 224]);
+        assert_eq!(batch.get(2)?.to_vec3::<f32>()?, load_image224(paths[2])?.to_vec3::<f32>()?);
+
+        let err = load_image224_batch(&[paths[0], "missing_face.png"]).unwrap_err();
+        assert!(matches!(&err, FaceAuthError::ImageLoad { path, .. } if path.as_str() == "missing_face.png"), "{err}");
+        assert!(load_image224_batch(&[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn custom_normalization_changes_only_the_constants() -> Result<()> {
+        let path = "../../../app/test_images/brad1.png";
+        let imagenet = load_image224_normalized(path, Normalization::default())?;
+        assert_eq!(imagenet.to_vec3::<f32>()?, load_image224(path)?.to_vec3::<f32>()?);
+
+        // Identity normalization leaves plain [0, 1] pixels
+        let identity = Normalization { mean: [0.0; 3], std: [1.0; 3] };
+        let raw = load_image224_normalized(path, identity)?;
+        let (min, max) = (raw.min_all()?.to_vec0::<f32>()?, raw.max_all()?.to_vec0::<f32>()?);
+        assert!(min >= 0.0 && max <= 1.0, "Pixels outside [0, 1]: {}..{}", min, max);
+
+        let mean = Tensor::new(&IMAGENET_MEAN, raw.device())?.reshape((3, 1, 1))?;
+        let std = Tensor::new(&IMAGENET_STD, raw.device())?.reshape((3, 1, 1))?;
+        let renormalized = raw.broadcast_sub(&mean)?.broadcast_div(&std)?;
+        let diff = (renormalized - &imagenet)?.abs()?.max_all()?.to_vec0::<f32>()?;
+        assert!(diff < 1e-5, "ImageNet output differs from renormalized identity output by {}", diff);
+        Ok(())
+    }
+
+    #[test]
+    fn exif_rotated_image_is_loaded_upright() -> Result<()> {
+        let raw = image::ImageReader::open("../../../app/test_images/brad1_rotated_exif.jpg")?.decode()?;
+        assert!(raw.width() > raw.height(), "Fixture should be stored sideways");
+
+        let upright = load_image224("../../../app/test_images/brad1.png")?;
+        let rotated = load_image224("../../../app/test_images/brad1_rotated_exif.jpg")?;
+        let sideways = image_with_std_mean(&raw, 224, &IMAGENET_MEAN, &IMAGENET_STD)?;
+        assert_eq!(rotated.dims(), &[3, 224, 224]);
+
+        let diff_rotated = (&upright - &rotated)?.abs()?.mean_all()?.to_vec0::<f32>()?;
+        let diff_sideways = (&upright - &sideways)?.abs()?.mean_all()?.to_vec0::<f32>()?;
+        assert!(diff_rotated < 0.1, "Oriented image differs from upright original by {}", diff_rotated);
+        assert!(diff_rotated < diff_sideways);
+        Ok(())
+    }
+
+    #[test]
+    fn image_without_exif_is_unchanged() -> Result<()> {
+        let raw = image::ImageReader::open("../../../app/test_images/tom.png")?.decode()?;
+        let expected = image_with_std_mean(&raw, 224, &IMAGENET_MEAN, &IMAGENET_STD)?;
+        let loaded = load_image224("../../../app/test_images/tom.png")?;
+        let diff = (expected - loaded)?.abs()?.max_all()?.to_vec0::<f32>()?;
+        assert_eq!(diff, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn bytes_and_path_loading_agree() -> Result<()> {
+        for path in ["../../../app/test_images/brad1.png", "../../../app/test_images/brad1_rotated_exif.jpg"] {
+            let bytes: Vec<u8> = std::fs::read(path)?;
+            let from_bytes = load_image224_from_bytes(&bytes)?;
+            let from_path = load_image224(path)?;
+            let diff = (from_bytes - from_path)?.abs()?.max_all()?.to_vec0::<f32>()?;
+            assert_eq!(diff, 0.0, "Byte and path loading differ for {}", path);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bytes_loading_rejects_bad_data() -> Result<()> {
+        assert!(load_image224_from_bytes(b"definitely not an image").is_err());
+
+        let bytes = std::fs::read("../../../app/test_images/brad1.png")?;
+        assert!(load_image224_from_bytes(&bytes[..bytes.len() / 4]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn load_image_respects_requested_size() -> Result<()> {
+        let path = "../../../app/test_images/brad2.png";
+        assert_eq!(load_image(path, 112)?.dims(), &[3, 112, 112]);
+        assert_eq!(load_image(path, 224)?.dims(), &[3, 224, 224]);
+        assert_eq!(load_image224(path)?.dims(), &[3, 224, 224]);
+
+        let identity = load_image_with_std_mean(path, 112, &[0.0; 3], &[1.0; 3])?;
+        let max = identity.max_all()?.to_vec0::<f32>()?;
+        let min = identity.min_all()?.to_vec0::<f32>()?;
+        assert!(min >= 0.0 && max <= 1.0, "Identity normalization should keep values in [0, 1]");
+        Ok(())
+    }
+
+    #[test]
+    fn missing_image_is_an_image_load_error() {
+        match load_image224("../../../app/test_images/does_not_exist.png") {
+            Err(FaceAuthError::ImageLoad { path, .. }) => assert!(path.ends_with("does_not_exist.png")),
+            other => panic!("Expected ImageLoad, got {:?}", other.map(|t| t.dims().to_vec())),
+        }
+    }
+
+    #[test]
+    fn crop_modes_differ_on_non_square_image() -> Result<()> {
+        let path = "../../../app/test_images/brad1_rotated_exif.jpg";
+        let raw = image::ImageReader::open(path)?.decode()?;
+        assert_ne!(raw.width(), raw.height(), "Fixture should not be square");
+
+        let stretched = load_image224_with(path, CropMode::Stretch)?;
+        let cropped = load_image224_with(path, CropMode::CenterCrop)?;
+        assert_eq!(stretched.dims(), &[3, 224, 224]);
+        assert_eq!(cropped.dims(), stretched.dims());
+
+        let diff = (&stretched - &cropped)?.abs()?.mean_all()?.to_vec0::<f32>()?;
+        assert!(diff > 0.01, "Stretch and center crop should produce different pixels, diff {}", diff);
+        let default = (load_image224(path)? - &cropped)?.abs()?.max_all()?.to_vec0::<f32>()?;
+        assert_eq!(default, 0.0, "load_image224 keeps the center-crop behaviour");
+        Ok(())
+    }
+
+    // Write `bytes` to a scratch file and check loading it fails with ImageLoad for that path
+    fn assert_image_load_error(name: &str, bytes: &[u8]) -> Result<()> {
+        let path = std::env::temp_dir().join(format!("face_auth_{}_{}", std::process::id(), name));
+        std::fs::write(&path, bytes)?;
+        let path = path.to_string_lossy().into_owned();
+        let result = load_image224(&path);
+        let _ = std::fs::remove_file(&path);
+        match result {
+            Err(FaceAuthError::ImageLoad { path: reported, source }) => {
+                assert_eq!(reported, path);
+                assert!(!source.to_string().is_empty(), "Cause should be reported for {}", name);
+            }
+            other => panic!("Expected ImageLoad for {}, got {:?}", name, other.map(|t| t.dims().to_vec())),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_files_are_image_load_errors() -> Result<()> {
+        let png = std::fs::read("../../../app/test_images/brad1.png")?;
+        assert_image_load_error("truncated.png", &png[..png.len() / 2])?;
+        assert_image_load_error("header_only.png", &png[..16])?;
+        assert_image_load_error("empty.png", &[])?;
+        assert_image_load_error("notes.png", b"this is a text file, not a picture")?;
+
+        let jpg = std::fs::read("../../../app/test_images/brad1_rotated_exif.jpg")?;
+        assert_image_load_error("truncated.jpg", &jpg[..jpg.len() / 3])?;
+        assert_image_load_error("no_end_marker.jpg", &jpg[..jpg.len() - 2])?;
+        Ok(())
+    }
+
+    #[test]
+    fn data_after_the_jpeg_end_marker_is_ignored() -> Result<()> {
+        let mut jpg = std::fs::read("../../../app/test_images/brad1_rotated_exif.jpg")?;
+        let original = load_image224_from_bytes(&jpg)?;
+        // e.g. a "motion photo" with a video appended after the still image
+        jpg.extend(std::iter::repeat_n(0x42, 4096));
+        let padded = load_image224_from_bytes(&jpg)?;
+        assert_eq!((original - padded)?.abs()?.max_all()?.to_vec0::<f32>()?, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn grayscale_image_is_broadcast_to_three_channels() -> Result<()> {
+        let path = "../../../app/test_images/brad1_gray.jpg";
+        let raw = image::ImageReader::open(path)?.decode()?;
+        assert_eq!(raw.color(), image::ColorType::L8, "Fixture should be single-channel");
+
+        let gray = load_image_with_std_mean(path, 224, &[0.0; 3], &[1.0; 3])?;
+        assert_eq!(gray.dims(), &[3, 224, 224]);
+        let red = gray.get(0)?;
+        for channel in 1..3 {
+            let diff = (&red - gray.get(channel)?)?.abs()?.max_all()?.to_vec0::<f32>()?;
+            assert_eq!(diff, 0.0, "Channel {} differs from channel 0", channel);
+        }
+
+        let color = load_image_with_std_mean("../../../app/test_images/brad1.png", 224, &[0.0; 3], &[1.0; 3])?;
+        let diff = (color.mean(0)? - gray.mean(0)?)?.abs()?.mean_all()?.to_vec0::<f32>()?;
+        assert!(diff < 0.05, "Grayscale fixture should match the colour photo's brightness, diff {}", diff);
+        Ok(())
+    }
+
+    #[test]
+    fn alpha_channel_is_dropped() -> Result<()> {
+        let raw = image::ImageReader::open("../../../app/test_images/tom.png")?.decode()?;
+        let rgba = DynamicImage::ImageRgba8(raw.to_rgba8());
+        let expected = image_with_std_mean(&raw, 224, &IMAGENET_MEAN, &IMAGENET_STD)?;
+        let loaded = image_with_std_mean(&rgba, 224, &IMAGENET_MEAN, &IMAGENET_STD)?;
+        assert_eq!(loaded.dims(), &[3, 224, 224]);
+        let diff = (expected - loaded)?.abs()?.max_all()?.to_vec0::<f32>()?;
+        assert_eq!(diff, 0.0);
+        Ok(())
+    }
+}
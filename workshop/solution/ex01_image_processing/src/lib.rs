@@ -2,6 +2,23 @@ use anyhow::Result;
 use candle_core::{Device, DType, Tensor};
 use image::{DynamicImage};
 
+pub mod augment;
+pub mod detect;
+pub mod imagenet;
+pub mod liveness;
+pub mod quality;
+
+/// How a non-square image is brought to the square network input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CropMode {
+    /// Scale the whole image to (res, res), distorting its aspect ratio.
+    Stretch,
+    /// Scale the shorter side to `res` and keep the centered square; this is what
+    /// `image_with_std_mean` has always done.
+    #[default]
+    CenterCrop,
+}
+
 /// Exercise goal: implement image loading + ImageNet normalization.
 /// Steps:
 /// - open image path with `image::ImageReader`
@@ -13,12 +30,24 @@ pub fn image_with_std_mean(
     mean: &[f32; 3],
     std: &[f32; 3],
 ) -> Result<Tensor> {
-    let img = img
-        .resize_to_fill(
-            res as u32,
-            res as u32,
-            image::imageops::FilterType::Triangle,
-        );
+    image_with_std_mean_cropped(img, res, mean, std, CropMode::CenterCrop)
+}
+
+/// Same as `image_with_std_mean`, with the crop behaviour chosen by `mode`.
+/// Any colour type is accepted: grayscale is replicated into all three channels, alpha is
+/// dropped and 16-bit or float pixels are scaled to 8 bits, so the tensor is always (3, res, res).
+pub fn image_with_std_mean_cropped(
+    img: &DynamicImage,
+    res: usize,
+    mean: &[f32; 3],
+    std: &[f32; 3],
+    mode: CropMode,
+) -> Result<Tensor> {
+    let filter = image::imageops::FilterType::Triangle;
+    let img = match mode {
+        CropMode::Stretch => img.resize_exact(res as u32, res as u32, filter),
+        CropMode::CenterCrop => img.resize_to_fill(res as u32, res as u32, filter),
+    };
     let img = img.to_rgb8();
     let data = img.into_raw();
     let data = Tensor::from_vec(data, (res, res, 3), &Device::Cpu)?.permute((2, 0, 1))?;
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use candle_core::Tensor;
+
+use crate::quality::denormalized_luma;
+
+/// High-frequency energy (on [0, 1] luma) at which `TextureLiveness` reaches 1 - 1/e.
+pub const DEFAULT_TEXTURE_SCALE: f32 = 0.0005;
+
+/// Anti-spoofing check run on the probe image before it is identified.
+pub trait Liveness {
+    /// Score how likely `img` is a live face rather than a reproduction, from 0 (spoof) to 1 (live).
+    /// Takes the ImageNet-normalized (3, H, W) tensor returned by the `imagenet` loaders.
+    fn check(&self, img: &Tensor) -> Result<f32>;
+}
+
+/// Flags flat reproductions (prints, screens held up to the camera) by how much fine texture
+/// survives: the energy of the luma minus its 3x3 box blur. Skin, hair and stubble keep a lot of
+/// it; re-photographed prints lose it to the print process and the second lens.
+///
+/// This is a heuristic, not a trained detector: it rejects the crudest attacks and a blurry live
+/// shot can score low too, so pair it with `quality::score` when choosing a threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureLiveness {
+    pub scale: f32,
+}
+
+impl Default for TextureLiveness {
+    fn default() -> Self {
+        TextureLiveness { scale: DEFAULT_TEXTURE_SCALE }
+    }
+}
+
+impl Liveness for TextureLiveness {
+    fn check(&self, img: &Tensor) -> Result<f32> {
+        let luma = denormalized_luma(img)?;
+        Ok(1.0 - (-high_frequency_energy(&luma) / self.scale).exp())
+    }
+}
+
+fn high_frequency_energy(luma: &[Vec<f32>]) -> f32 {
+    let (height, width) = (luma.len(), luma[0].len());
+    let mut total = 0.0;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let blurred = (y - 1..=y + 1)
+                .flat_map(|yy| (x - 1..=x + 1).map(move |xx| (yy, xx)))
+                .map(|(yy, xx)| luma[yy][xx])
+                .sum::<f32>()
+                / 9.0;
+            total += (luma[y][x] - blurred).powi(2);
+        }
+    }
+    total / ((height - 2) * (width - 2)) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imagenet::{load_image224, IMAGENET_MEAN, IMAGENET_STD};
+    use crate::image_with_std_mean;
+
+    #[test]
+    fn flat_image_scores_lower_than_a_photo() -> Result<()> {
+        let liveness = TextureLiveness::default();
+        // A smooth vertical gradient: well exposed, but with no fine texture at all
+        let flat = image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(224, 224, |_, y| {
+            let v = 60 + (y * 140 / 224) as u8;
+            image::Rgb([v, v.saturating_sub(10), v.saturating_sub(20)])
+        }));
+        let flat_score = liveness.check(&image_with_std_mean(&flat, 224, &IMAGENET_MEAN, &IMAGENET_STD)?)?;
+
+        for path in ["../../../app/test_images/brad1.png", "../../../app/test_images/tom.png"] {
+            let photo_score = liveness.check(&load_image224(path)?)?;
+            assert!(flat_score < photo_score, "{}: flat {} should score below photo {}", path, flat_score, photo_score);
+            assert!((0.0..=1.0).contains(&photo_score));
+        }
+        Ok(())
+    }
+}
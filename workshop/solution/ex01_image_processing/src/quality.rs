@@ -0,0 +1,161 @@
+use anyhow::Result;
+use candle_core::{DType, Tensor};
+
+use crate::imagenet::{load_image224, IMAGENET_MEAN, IMAGENET_STD};
+
+/// Laplacian variance (on [0, 1] luma) at which sharpness reaches 1 - 1/e.
+const SHARPNESS_SCALE: f32 = 0.002;
+/// Luma below / above these counts as crushed black / blown-out white.
+const CLIP_LOW: f32 = 0.02;
+const CLIP_HIGH: f32 = 0.98;
+
+/// Estimate how usable a face crop is for enrollment, from 0 (useless) to 1 (good).
+///
+/// Expects an ImageNet-normalized (3, H, W) tensor as returned by the `imagenet` loaders.
+/// The score multiplies a sharpness term (variance of the Laplacian, low for blurry shots) by an
+/// exposure term (mean brightness near mid-grey, few clipped pixels). Plain CPU arithmetic, so the
+/// same tensor always gets the same score.
+pub fn score(img: &Tensor) -> Result<f32> {
+    let luma = denormalized_luma(img)?;
+    Ok(sharpness(&luma) * exposure(&luma))
+}
+
+/// Whether `score(img)` reaches `min_score`.
+pub fn is_acceptable(img: &Tensor, min_score: f32) -> Result<bool> {
+    Ok(score(img)? >= min_score)
+}
+
+/// Pick the frame of a burst that scores highest, returning its index and its `load_image224`
+/// tensor. The earliest frame wins a tie; an empty burst is an error.
+pub fn best_frame(paths: &[&str]) -> Result<(usize, Tensor)> {
+    let mut best: Option<(usize, Tensor, f32)> = None;
+    for (index, path) in paths.iter().enumerate() {
+        let frame = load_image224(path)?;
+        let frame_score = score(&frame)?;
+        if best.as_ref().is_none_or(|(_, _, best_score)| frame_score > *best_score) {
+            best = Some((index, frame, frame_score));
+        }
+    }
+    best.map(|(index, frame, _)| (index, frame))
+        .ok_or_else(|| anyhow::anyhow!("Cannot pick the best frame of an empty burst"))
+}
+
+/// Undo the ImageNet normalization and collapse to Rec. 601 luma rows in [0, 1].
+pub(crate) fn denormalized_luma(img: &Tensor) -> Result<Vec<Vec<f32>>> {
+    let (channels, height, width) = img.dims3()?;
+    if channels != 3 {
+        anyhow::bail!("Expected a (3, H, W) image tensor, got {} channels", channels);
+    }
+    if height < 3 || width < 3 {
+        anyhow::bail!("Image of {}x{} is too small to score", width, height);
+    }
+    let planes = img.to_dtype(DType::F32)?.to_vec3::<f32>()?;
+    let weights = [0.299f32, 0.587, 0.114];
+    Ok((0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    (0..3)
+                        .map(|c| weights[c] * (planes[c][y][x] * IMAGENET_STD[c] + IMAGENET_MEAN[c]))
+                        .sum::<f32>()
+                        .clamp(0.0, 1.0)
+                })
+                .collect()
+        })
+        .collect())
+}
+
+fn sharpness(luma: &[Vec<f32>]) -> f32 {
+    let (height, width) = (luma.len(), luma[0].len());
+    let mut responses = Vec::with_capacity((height - 2) * (width - 2));
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let centre = luma[y][x];
+            responses.push(4.0 * centre - luma[y - 1][x] - luma[y + 1][x] - luma[y][x - 1] - luma[y][x + 1]);
+        }
+    }
+    let mean = responses.iter().sum::<f32>() / responses.len() as f32;
+    let variance = responses.iter().map(|r| (r - mean).powi(2)).sum::<f32>() / responses.len() as f32;
+    1.0 - (-variance / SHARPNESS_SCALE).exp()
+}
+
+fn exposure(luma: &[Vec<f32>]) -> f32 {
+    let pixels = luma.iter().flatten();
+    let count = (luma.len() * luma[0].len()) as f32;
+    let mean = pixels.clone().sum::<f32>() / count;
+    let clipped = pixels.filter(|&&v| !(CLIP_LOW..=CLIP_HIGH).contains(&v)).count() as f32 / count;
+    (1.0 - 2.0 * (mean - 0.5).abs()).max(0.0) * (1.0 - clipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_with_std_mean;
+
+    fn fixture(path: &str) -> Result<image::DynamicImage> {
+        Ok(image::ImageReader::open(path)?.decode()?)
+    }
+
+    fn tensor(img: &image::DynamicImage) -> Result<Tensor> {
+        image_with_std_mean(img, 224, &IMAGENET_MEAN, &IMAGENET_STD)
+    }
+
+    #[test]
+    fn blurred_photo_scores_lower_than_sharp_one() -> Result<()> {
+        for path in ["../../../app/test_images/brad1.png", "../../../app/test_images/tom.png"] {
+            let sharp = fixture(path)?.resize_to_fill(224, 224, image::imageops::FilterType::Triangle);
+            let blurred = sharp.blur(3.0);
+            let (sharp_score, blurred_score) = (score(&tensor(&sharp)?)?, score(&tensor(&blurred)?)?);
+            assert!(
+                blurred_score < sharp_score,
+                "{}: blurred {} should score below sharp {}",
+                path,
+                blurred_score,
+                sharp_score
+            );
+            assert!((0.0..=1.0).contains(&sharp_score));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn badly_exposed_photo_is_rejected() -> Result<()> {
+        let img = fixture("../../../app/test_images/brad2.png")?.resize_to_fill(224, 224, image::imageops::FilterType::Triangle);
+        let good = tensor(&img)?;
+        let blown_out = tensor(&img.brighten(160))?;
+        let dark = tensor(&img.brighten(-160))?;
+        assert!(score(&blown_out)? < score(&good)?);
+        assert!(score(&dark)? < score(&good)?);
+        assert_eq!(score(&good)?, score(&good)?, "Scoring must be deterministic");
+        assert!(is_acceptable(&good, 0.5)?);
+        assert!(!is_acceptable(&blown_out, 0.5)?);
+        Ok(())
+    }
+
+    #[test]
+    fn best_frame_picks_the_sharpest_shot() -> Result<()> {
+        let sharp_path = "../../../app/test_images/brad1.png";
+        let sharp = fixture(sharp_path)?.resize_to_fill(224, 224, image::imageops::FilterType::Triangle);
+        let dir = std::env::temp_dir();
+        let blurry_paths: Vec<String> = [2.0, 4.0]
+            .iter()
+            .map(|sigma| {
+                let path = dir.join(format!("face_auth_burst_{}_{}.png", std::process::id(), sigma));
+                sharp.blur(*sigma).save(&path)?;
+                Ok(path.to_string_lossy().into_owned())
+            })
+            .collect::<Result<_>>()?;
+
+        let burst = [blurry_paths[0].as_str(), sharp_path, blurry_paths[1].as_str()];
+        let (index, frame) = best_frame(&burst)?;
+        for path in &blurry_paths {
+            std::fs::remove_file(path)?;
+        }
+        assert_eq!(index, 1);
+        assert_eq!(frame.dims(), &[3, 224, 224]);
+
+        assert_eq!(best_frame(&[sharp_path])?.0, 0);
+        assert!(best_frame(&[]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+//! Fetching model weights with retries. The fetcher is a trait so tests (or mirrors) can
+//! stand in for the Hugging Face Hub.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Environment variable naming the directory `build_model` caches weights in.
+pub const CACHE_DIR_ENV: &str = "FACE_AUTH_MODEL_CACHE";
+
+/// Upper bound for a single backoff pause.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Something that can produce a local copy of a weights file.
+pub trait WeightFetcher {
+    fn fetch(&self) -> Result<PathBuf>;
+}
+
+/// Downloads one file of a Hub model repo into the standard `hf-hub` cache.
+pub struct HubFetcher {
+    pub repo: String,
+    pub file: String,
+}
+
+impl Default for HubFetcher {
+    fn default() -> Self {
+        HubFetcher {
+            repo: "timm/convnext_atto.d2_in1k".to_string(),
+            file: "model.safetensors".to_string(),
+        }
+    }
+}
+
+impl WeightFetcher for HubFetcher {
+    fn fetch(&self) -> Result<PathBuf> {
+        let api = hf_hub::api::sync::Api::new()?;
+        Ok(api.model(self.repo.clone()).get(&self.file)?)
+    }
+}
+
+/// Keeps a copy of the weights in `cache_dir` next to a `.sha256` file holding its checksum.
+/// A cached copy whose checksum still matches is returned without calling `inner`; a missing
+/// or corrupt one is replaced by a fresh fetch.
+pub struct CachedFetcher<F> {
+    pub cache_dir: PathBuf,
+    pub file_name: String,
+    pub inner: F,
+}
+
+impl<F: WeightFetcher> CachedFetcher<F> {
+    pub fn new(cache_dir: impl Into<PathBuf>, file_name: &str, inner: F) -> Self {
+        CachedFetcher { cache_dir: cache_dir.into(), file_name: file_name.to_string(), inner }
+    }
+
+    pub fn cached_path(&self) -> PathBuf {
+        self.cache_dir.join(&self.file_name)
+    }
+
+    fn checksum_path(&self) -> PathBuf {
+        self.cache_dir.join(format!("{}.sha256", self.file_name))
+    }
+
+    // The cached file, if present and its checksum matches the recorded one
+    fn valid_cached(&self) -> Option<PathBuf> {
+        let path = self.cached_path();
+        let expected = fs::read_to_string(self.checksum_path()).ok()?;
+        let actual = sha256_hex(&path).ok()?;
+        (expected.trim() == actual).then_some(path)
+    }
+}
+
+impl<F: WeightFetcher> WeightFetcher for CachedFetcher<F> {
+    fn fetch(&self) -> Result<PathBuf> {
+        if let Some(path) = self.valid_cached() {
+            return Ok(path);
+        }
+        let _ = fs::remove_file(self.cached_path());
+        let _ = fs::remove_file(self.checksum_path());
+
+        let downloaded = self.inner.fetch()?;
+        fs::create_dir_all(&self.cache_dir)
+            .with_context(|| format!("Failed to create cache directory {}", self.cache_dir.display()))?;
+        // Copy under a temporary name first so an interrupted copy never looks like a cached file
+        let path = self.cached_path();
+        let tmp_path = self.cache_dir.join(format!("{}.tmp", self.file_name));
+        fs::copy(&downloaded, &tmp_path)?;
+        fs::rename(&tmp_path, &path)?;
+        fs::write(self.checksum_path(), sha256_hex(&path)?)?;
+        Ok(path)
+    }
+}
+
+fn sha256_hex(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Call `fetcher` up to `max_attempts` times, sleeping `initial_backoff`, then twice as long,
+/// and so on between attempts. A fetched file that is not a complete safetensors file is
+/// deleted before the next attempt so it is downloaded again from scratch.
+pub fn fetch_with_retry(fetcher: &dyn WeightFetcher, max_attempts: usize, initial_backoff: Duration) -> Result<PathBuf> {
+    let max_attempts = max_attempts.max(1);
+    let mut backoff = initial_backoff;
+    let mut last_error = None;
+    for attempt in 1..=max_attempts {
+        match fetcher.fetch().and_then(|path| validate_safetensors(&path).map(|_| path)) {
+            Ok(path) => return Ok(path),
+            Err(e) => last_error = Some(e),
+        }
+        if attempt < max_attempts {
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+    let error = last_error.expect("at least one attempt is made");
+    Err(error.context(format!("Failed to fetch model weights after {max_attempts} attempt(s)")))
+}
+
+/// Check that `path` holds a well-formed safetensors file whose header covers exactly the
+/// bytes on disk, which catches truncated downloads. An invalid file is removed.
+pub fn validate_safetensors(path: &Path) -> Result<()> {
+    // The mapping is only used to parse the header and is dropped before anything else touches the file
+    let parsed = unsafe { candle_core::safetensors::MmapedSafetensors::new(path) };
+    if let Err(e) = parsed {
+        remove_download(path);
+        return Err(e).with_context(|| format!("Invalid weights file {}, deleted it", path.display()));
+    }
+    Ok(())
+}
+
+// hf-hub caches snapshots as symlinks into a blob store, so drop both the link and its target
+fn remove_download(path: &Path) {
+    if let Ok(target) = fs::canonicalize(path) {
+        let _ = fs::remove_file(target);
+    }
+    let _ = fs::remove_file(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::{Device, Tensor};
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    // Fails `failures` times, then writes a small valid safetensors file; optionally hands back
+    // a truncated copy on the first successful-looking call
+    struct FlakyFetcher {
+        path: PathBuf,
+        failures: usize,
+        truncate_first: bool,
+        calls: Cell<usize>,
+    }
+
+    impl WeightFetcher for FlakyFetcher {
+        fn fetch(&self) -> Result<PathBuf> {
+            let call = self.calls.get() + 1;
+            self.calls.set(call);
+            if call <= self.failures {
+                anyhow::bail!("connection reset (attempt {call})");
+            }
+            let weights = HashMap::from([("w".to_string(), Tensor::ones((4, 4), candle_core::DType::F32, &Device::Cpu)?)]);
+            candle_core::safetensors::save(&weights, &self.path)?;
+            if self.truncate_first && call == self.failures + 1 {
+                let bytes = std::fs::read(&self.path)?;
+                std::fs::write(&self.path, &bytes[..bytes.len() - 10])?;
+            }
+            Ok(self.path.clone())
+        }
+    }
+
+    fn scratch_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("face_auth_{}_{}.safetensors", std::process::id(), name))
+    }
+
+    #[test]
+    fn transient_failures_are_retried_until_success() -> Result<()> {
+        let fetcher = FlakyFetcher { path: scratch_path("flaky"), failures: 2, truncate_first: false, calls: Cell::new(0) };
+        let path = fetch_with_retry(&fetcher, 3, Duration::from_millis(1))?;
+        assert_eq!(fetcher.calls.get(), 3);
+        assert!(path.exists());
+        std::fs::remove_file(path)?;
+
+        let fetcher = FlakyFetcher { path: scratch_path("hopeless"), failures: 5, truncate_first: false, calls: Cell::new(0) };
+        let err = fetch_with_retry(&fetcher, 3, Duration::from_millis(1)).unwrap_err();
+        assert_eq!(fetcher.calls.get(), 3);
+        assert!(format!("{err:#}").contains("connection reset"), "Last cause should be kept: {err:#}");
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_download_is_deleted_and_fetched_again() -> Result<()> {
+        let path = scratch_path("truncated");
+        let fetcher = FlakyFetcher { path: path.clone(), failures: 0, truncate_first: true, calls: Cell::new(0) };
+        assert!(fetch_with_retry(&fetcher, 1, Duration::ZERO).is_err());
+        assert!(!path.exists(), "Partial file should be removed");
+
+        let fetcher = FlakyFetcher { path: path.clone(), failures: 0, truncate_first: true, calls: Cell::new(0) };
+        fetch_with_retry(&fetcher, 2, Duration::from_millis(1))?;
+        assert_eq!(fetcher.calls.get(), 2);
+        validate_safetensors(&path)?;
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    // Writes a fresh valid weights file on every call and counts the calls
+    struct CountingFetcher {
+        path: PathBuf,
+        calls: Cell<usize>,
+    }
+
+    impl WeightFetcher for CountingFetcher {
+        fn fetch(&self) -> Result<PathBuf> {
+            self.calls.set(self.calls.get() + 1);
+            let weights = HashMap::from([("w".to_string(), Tensor::ones(3, candle_core::DType::F32, &Device::Cpu)?)]);
+            candle_core::safetensors::save(&weights, &self.path)?;
+            Ok(self.path.clone())
+        }
+    }
+
+    #[test]
+    fn cache_is_reused_until_its_checksum_breaks() -> Result<()> {
+        let cache_dir = std::env::temp_dir().join(format!("face_auth_cache_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&cache_dir);
+        let source = scratch_path("source");
+        let cached = CachedFetcher::new(&cache_dir, "weights.safetensors", CountingFetcher { path: source.clone(), calls: Cell::new(0) });
+
+        let first = fetch_with_retry(&cached, 1, Duration::ZERO)?;
+        let second = fetch_with_retry(&cached, 1, Duration::ZERO)?;
+        assert_eq!(first, second);
+        assert_eq!(first, cached.cached_path());
+        assert_eq!(cached.inner.calls.get(), 1, "Second build should use the cached file");
+
+        // Flip a byte: the checksum no longer matches, so the file is fetched again
+        let mut bytes = fs::read(&first)?;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&first, bytes)?;
+        fetch_with_retry(&cached, 1, Duration::ZERO)?;
+        assert_eq!(cached.inner.calls.get(), 2);
+        validate_safetensors(&first)?;
+
+        fs::remove_dir_all(&cache_dir)?;
+        fs::remove_file(source)?;
+        Ok(())
+    }
+}
@@ -2,22 +2,130 @@ use anyhow::Result;
 use candle_core::{DType, Device, Tensor};
 use candle_nn::{Module, VarBuilder, Func};
 use candle_transformers::models::convnext;
+use download::{fetch_with_retry, CachedFetcher, HubFetcher, WeightFetcher, CACHE_DIR_ENV};
+use face_auth_error::FaceAuthError;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-pub fn build_model() -> Result<Func<'static>> {
-    let device = &Device::Cpu;
-    let model_file = {
-        let api = hf_hub::api::sync::Api::new()?;
-        let api = api.model("timm/convnext_atto.d2_in1k".to_string());
-        api.get("model.safetensors")?
-    };
+pub mod download;
+mod pool;
+pub use pool::{ModelGuard, ModelPool};
+#[cfg(feature = "onnx")]
+pub mod onnx;
+
+/// Pause before the second download attempt of `build_model_with_retry`; doubled after each failure.
+const RETRY_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// File name of the weights inside a `build_model_cached` directory.
+const MODEL_CACHE_FILE: &str = "convnext_atto.d2_in1k.safetensors";
+
+pub fn build_model() -> Result<Func<'static>, FaceAuthError> {
+    build_model_on(&Device::Cpu)
+}
+
+/// Build the model with its weights loaded onto `device`.
+/// Input tensors passed to `compute_embedding` must live on the same device.
+/// Weights are cached in `$FACE_AUTH_MODEL_CACHE` when it is set, otherwise in the hf-hub cache.
+/// Download and weight-loading failures are reported as `ModelLoad`.
+pub fn build_model_on(device: &Device) -> Result<Func<'static>, FaceAuthError> {
+    build_with(&*default_fetcher(), 1, Duration::ZERO, device)
+}
+
+/// Like `build_model`, but the weight download is tried up to `max_attempts` times with
+/// exponential backoff. Incomplete downloads are deleted so each retry starts clean.
+pub fn build_model_with_retry(max_attempts: usize) -> Result<Func<'static>, FaceAuthError> {
+    build_with(&*default_fetcher(), max_attempts, RETRY_INITIAL_BACKOFF, &Device::Cpu)
+}
+
+/// Like `build_model`, keeping the weights in `cache_dir`. A cached copy whose checksum
+/// matches is reused; a corrupt one is downloaded again.
+pub fn build_model_cached(cache_dir: impl AsRef<Path>) -> Result<Func<'static>, FaceAuthError> {
+    let fetcher = CachedFetcher::new(cache_dir.as_ref(), MODEL_CACHE_FILE, HubFetcher::default());
+    build_with(&fetcher, 1, Duration::ZERO, &Device::Cpu)
+}
+
+fn default_fetcher() -> Box<dyn WeightFetcher> {
+    match std::env::var_os(CACHE_DIR_ENV) {
+        Some(dir) => Box::new(CachedFetcher::new(dir, MODEL_CACHE_FILE, HubFetcher::default())),
+        None => Box::new(HubFetcher::default()),
+    }
+}
 
-    let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[model_file], DType::F32, &device)? };
+fn build_with(fetcher: &dyn WeightFetcher, max_attempts: usize, backoff: Duration, device: &Device) -> Result<Func<'static>, FaceAuthError> {
+    let model_file = fetch_with_retry(fetcher, max_attempts, backoff).map_err(|e| FaceAuthError::ModelLoad(e.into()))?;
+    load_convnext(model_file, device).map_err(|e| FaceAuthError::ModelLoad(e.into()))
+}
+
+/// Build the model from a local `.safetensors` file, without any network access.
+/// Every tensor the architecture needs must be present with the right shape; extra tensors
+/// (such as a classifier head) are ignored. Problems are reported as `ModelLoad`.
+pub fn build_model_from_safetensors(path: &str) -> Result<Func<'static>, FaceAuthError> {
+    check_weights(Path::new(path), &Device::Cpu)
+        .and_then(|_| load_convnext(PathBuf::from(path), &Device::Cpu))
+        .map_err(|e| FaceAuthError::ModelLoad(e.into()))
+}
+
+/// Compare the tensors in `path` with the names and shapes the architecture declares.
+fn check_weights(path: &Path, device: &Device) -> Result<()> {
+    // Building the model over an empty VarMap records every parameter it asks for
+    let expected = candle_nn::VarMap::new();
+    convnext::convnext_no_final_layer(&convnext::Config::atto(), VarBuilder::from_varmap(&expected, DType::F32, device))?;
+
+    let weights = unsafe { candle_core::safetensors::MmapedSafetensors::new(path)? };
+    let mut problems = Vec::new();
+    let mut names: Vec<(String, Vec<usize>)> = expected
+        .data()
+        .lock()
+        .map_err(|_| anyhow::anyhow!("VarMap lock poisoned"))?
+        .iter()
+        .map(|(name, var)| (name.clone(), var.dims().to_vec()))
+        .collect();
+    names.sort();
+    for (name, shape) in names {
+        match weights.get(&name) {
+            Ok(view) if view.shape() == shape.as_slice() => {}
+            Ok(view) => problems.push(format!("{name}: expected shape {shape:?}, found {:?}", view.shape())),
+            Err(_) => problems.push(format!("{name}: missing")),
+        }
+    }
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "{} does not match the convnext_atto architecture ({} problem(s)): {}",
+            path.display(),
+            problems.len(),
+            problems.join("; ")
+        );
+    }
+    Ok(())
+}
+
+fn load_convnext(model_file: PathBuf, device: &Device) -> Result<Func<'static>> {
+    let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[model_file], DType::F32, device)? };
     let model = convnext::convnext_no_final_layer(&convnext::Config::atto(), vb)?;
 
     Ok(model)
 }
 
-pub fn compute_embedding(model: &Func, image: &Tensor) -> Result<Tensor> {
+/// Pick the fastest device this build can use: CUDA, then Metal, then CPU.
+/// GPU backends are only available when the crate is built with the `cuda` or `metal` feature.
+pub fn best_available_device() -> Device {
+    if candle_core::utils::cuda_is_available() {
+        if let Ok(device) = Device::new_cuda(0) {
+            return device;
+        }
+    }
+    if candle_core::utils::metal_is_available() {
+        if let Ok(device) = Device::new_metal(0) {
+            return device;
+        }
+    }
+    Device::Cpu
+}
+
+pub fn compute_embedding(model: &Func, image: &Tensor) -> Result<Tensor, FaceAuthError> {
+    Ok(forward_one(model, image)?)
+}
+
+fn forward_one(model: &Func, image: &Tensor) -> Result<Tensor> {
     // If image is not a batch, unsqueeze it, else use as is
     let input = if image.dim(0)? == 3 {
         image.unsqueeze(0)?
@@ -28,3 +136,200 @@ pub fn compute_embedding(model: &Func, image: &Tensor) -> Result<Tensor> {
     let embeddings = model.forward(&input)?;
     Ok(embeddings)
 }
+
+/// Compute embeddings for several images with a single forward pass.
+/// Each input is a `[3, H, W]` (or `[1, 3, H, W]`) image tensor; the output keeps the
+/// input order and matches what `compute_embedding` returns for each image on its own.
+pub fn compute_embeddings(model: &Func, images: &[Tensor]) -> Result<Vec<Tensor>, FaceAuthError> {
+    Ok(forward_batch(model, images)?)
+}
+
+fn forward_batch(model: &Func, images: &[Tensor]) -> Result<Vec<Tensor>> {
+    if images.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let images = images
+        .iter()
+        .map(|image| if image.rank() == 4 { image.squeeze(0) } else { Ok(image.clone()) })
+        .collect::<candle_core::Result<Vec<_>>>()?;
+    let expected = images[0].dims();
+    if let Some((i, image)) = images.iter().enumerate().find(|(_, image)| image.dims() != expected) {
+        anyhow::bail!(
+            "Image {} has shape {:?}, expected {:?} like the first image",
+            i,
+            image.dims(),
+            expected
+        );
+    }
+
+    let batch = Tensor::stack(&images, 0)?;
+    let embeddings = model.forward(&batch)?;
+    (0..images.len())
+        .map(|i| Ok(embeddings.narrow(0, i, 1)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ex01_image_processing_solution::image_with_std_mean;
+    use ex01_image_processing_solution::imagenet::load_image224;
+
+    #[test]
+    fn batched_embeddings_match_single_image_path() -> Result<()> {
+        let model = build_model()?;
+        let imagenet_mean: [f32; 3] = [0.485, 0.456, 0.406];
+        let imagenet_std: [f32; 3] = [0.229, 0.224, 0.225];
+        let mut images = Vec::new();
+        for path in ["brad1.png", "brad2.png", "tom.png"] {
+            let reader = image::ImageReader::open(format!("../../../app/test_images/{path}"))?;
+            let image = reader.decode()?;
+            images.push(image_with_std_mean(&image, 224, &imagenet_mean, &imagenet_std)?);
+        }
+
+        let batched = compute_embeddings(&model, &images)?;
+        assert_eq!(batched.len(), images.len());
+        for (image, batched_emb) in images.iter().zip(&batched) {
+            let single = compute_embedding(&model, image)?;
+            assert_eq!(single.dims(), batched_emb.dims());
+            let diff = (single - batched_emb)?.abs()?.max_all()?.to_vec0::<f32>()?;
+            assert!(diff < 1e-5, "Batched embedding differs from single-image one by {}", diff);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn embedding_on_best_available_device() -> Result<()> {
+        let device = best_available_device();
+        let model = build_model_on(&device)?;
+        let reader = image::ImageReader::open("../../../app/test_images/brad1.png")?;
+        let image = reader.decode()?;
+        let imagenet_mean: [f32; 3] = [0.485, 0.456, 0.406];
+        let imagenet_std: [f32; 3] = [0.229, 0.224, 0.225];
+        let img = image_with_std_mean(&image, 224, &imagenet_mean, &imagenet_std)?.to_device(&device)?;
+        let emb = compute_embedding(&model, &img)?;
+        assert!(emb.device().same_device(&device));
+        assert_eq!(emb.dims(), &[1, 320]);
+        Ok(())
+    }
+
+    #[test]
+    fn exif_rotated_embedding_matches_upright() -> Result<()> {
+        let model = build_model()?;
+        let upright = compute_embedding(&model, &load_image224("../../../app/test_images/brad1.png")?)?;
+        let rotated = compute_embedding(&model, &load_image224("../../../app/test_images/brad1_rotated_exif.jpg")?)?;
+        let dot = (&upright * &rotated)?.sum_all()?.to_vec0::<f32>()?;
+        let norms = upright.sqr()?.sum_all()?.sqrt()?.to_vec0::<f32>()? * rotated.sqr()?.sum_all()?.sqrt()?.to_vec0::<f32>()?;
+        let similarity = dot / norms;
+        assert!(similarity > 0.9, "Rotated photo embedding is too far from upright one: {}", similarity);
+        Ok(())
+    }
+
+    #[test]
+    fn compute_embeddings_handles_empty_and_mismatched_input() -> Result<()> {
+        // A stand-in model so the shape handling can be tested without downloading weights
+        let model = Func::new(|xs| xs.flatten_from(1));
+        assert!(compute_embeddings(&model, &[])?.is_empty());
+
+        let a = Tensor::zeros((3, 8, 8), DType::F32, &Device::Cpu)?;
+        let b = Tensor::zeros((3, 4, 4), DType::F32, &Device::Cpu)?;
+        assert!(compute_embeddings(&model, &[a.clone(), b]).is_err());
+
+        let out = compute_embeddings(&model, &[a.clone(), a])?;
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0].dims(), &[1, 3 * 8 * 8]);
+        Ok(())
+    }
+
+    #[test]
+    fn grayscale_embedding_matches_colour_photo() -> Result<()> {
+        let model = build_model()?;
+        let colour = compute_embedding(&model, &load_image224("../../../app/test_images/brad1.png")?)?;
+        let gray = compute_embedding(&model, &load_image224("../../../app/test_images/brad1_gray.jpg")?)?;
+        assert_eq!(colour.dims(), gray.dims());
+        let dot = (&colour * &gray)?.sum_all()?.to_vec0::<f32>()?;
+        let norms = colour.sqr()?.sum_all()?.sqrt()?.to_vec0::<f32>()? * gray.sqr()?.sum_all()?.sqrt()?.to_vec0::<f32>()?;
+        let similarity = dot / norms;
+        assert!(similarity > 0.8, "Grayscale embedding is too far from the colour one: {}", similarity);
+        Ok(())
+    }
+
+    #[test]
+    fn mirrored_variant_embeds_close_to_original() -> Result<()> {
+        let model = build_model()?;
+        let original = load_image224("../../../app/test_images/brad1.png")?;
+        let variants = ex01_image_processing_solution::augment::variants(&original)?;
+        let embeddings = compute_embeddings(&model, &variants)?;
+        let (plain, mirrored) = (&embeddings[0], &embeddings[1]);
+        let dot = (plain * mirrored)?.sum_all()?.to_vec0::<f32>()?;
+        let norms = plain.sqr()?.sum_all()?.sqrt()?.to_vec0::<f32>()? * mirrored.sqr()?.sum_all()?.sqrt()?.to_vec0::<f32>()?;
+        let similarity = dot / norms;
+        assert!(similarity < 0.9999, "Mirrored variant embeds identically to the original");
+        assert!(similarity > 0.9, "Mirrored embedding is too far from the original: {}", similarity);
+        Ok(())
+    }
+
+    #[test]
+    fn cached_build_reuses_the_downloaded_weights() -> Result<()> {
+        let cache_dir = std::env::temp_dir().join(format!("face_auth_model_cache_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+        build_model_cached(&cache_dir)?;
+        let cached = cache_dir.join(MODEL_CACHE_FILE);
+        let first_write = std::fs::metadata(&cached)?.modified()?;
+
+        build_model_cached(&cache_dir)?;
+        assert_eq!(std::fs::metadata(&cached)?.modified()?, first_write, "Cached weights were fetched again");
+        std::fs::remove_dir_all(&cache_dir)?;
+        Ok(())
+    }
+
+    // Randomly initialised weights for the real architecture, so no download is needed
+    fn random_model_file(path: &Path) -> Result<candle_nn::VarMap> {
+        let varmap = candle_nn::VarMap::new();
+        convnext::convnext_no_final_layer(&convnext::Config::atto(), VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu))?;
+        varmap.save(path)?;
+        Ok(varmap)
+    }
+
+    #[test]
+    fn safetensors_round_trip_gives_identical_embeddings() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("face_auth_weights_{}.safetensors", std::process::id()));
+        let varmap = random_model_file(&path)?;
+        let original = convnext::convnext_no_final_layer(&convnext::Config::atto(), VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu))?;
+        let reloaded = build_model_from_safetensors(path.to_str().unwrap())?;
+        std::fs::remove_file(&path)?;
+
+        let image = load_image224("../../../app/test_images/brad1.png")?;
+        let a = compute_embedding(&original, &image)?;
+        let b = compute_embedding(&reloaded, &image)?;
+        assert_eq!(a.dims(), &[1, 320]);
+        let diff = (a - b)?.abs()?.max_all()?.to_vec0::<f32>()?;
+        assert_eq!(diff, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn safetensors_with_wrong_tensors_is_rejected() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("face_auth_bad_weights_{}.safetensors", std::process::id()));
+        random_model_file(&path)?;
+        let mut weights = candle_core::safetensors::load(&path, &Device::Cpu)?;
+        let mut names: Vec<String> = weights.keys().cloned().collect();
+        names.sort();
+        weights.remove(&names[0]);
+        weights.insert(names[1].clone(), Tensor::zeros(7, DType::F32, &Device::Cpu)?);
+        candle_core::safetensors::save(&weights, &path)?;
+
+        let result = build_model_from_safetensors(path.to_str().unwrap());
+        std::fs::remove_file(&path)?;
+        let message = match result {
+            Err(FaceAuthError::ModelLoad(e)) => e.to_string(),
+            Err(other) => panic!("Expected ModelLoad, got {other}"),
+            Ok(_) => panic!("Mismatched weights were accepted"),
+        };
+        assert!(message.contains(&format!("{}: missing", names[0])), "{}", message);
+        assert!(message.contains(&format!("{}: expected shape", names[1])), "{}", message);
+        assert!(matches!(build_model_from_safetensors("missing.safetensors"), Err(FaceAuthError::ModelLoad(_))));
+        Ok(())
+    }
+}
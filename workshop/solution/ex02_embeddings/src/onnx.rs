@@ -0,0 +1,78 @@
+//! Alternative backbone: any ONNX model taking a (1, 3, 224, 224) float image and returning
+//! one embedding vector, run through the pure-Rust `tract` runtime. Enabled by the `onnx` feature.
+
+use anyhow::Result;
+use candle_core::{Device, Tensor};
+use face_auth_error::FaceAuthError;
+use std::path::Path;
+use tract_onnx::prelude::{tvec, Datum, Framework, InferenceFact, InferenceModelExt, TypedModel, TypedRunnableModel};
+
+/// Side length of the square input the ONNX models are compiled for, matching `load_image224`.
+pub const ONNX_INPUT_SIZE: usize = 224;
+
+pub struct OnnxModel {
+    plan: TypedRunnableModel<TypedModel>,
+}
+
+impl OnnxModel {
+    /// Load and optimize an `.onnx` file for a single (1, 3, 224, 224) f32 input.
+    /// Unreadable or unsupported models are reported as `ModelLoad`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FaceAuthError> {
+        let input = InferenceFact::dt_shape(f32::datum_type(), [1, 3, ONNX_INPUT_SIZE, ONNX_INPUT_SIZE]);
+        let plan = tract_onnx::onnx()
+            .model_for_path(path)
+            .and_then(|model| model.with_input_fact(0, input))
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| FaceAuthError::ModelLoad(e.into()))?;
+        Ok(OnnxModel { plan })
+    }
+
+    /// Same contract as the candle `compute_embedding`: takes a `[3, 224, 224]` (or
+    /// `[1, 3, 224, 224]`) image as produced by `load_image224` and returns a `[1, dim]` embedding.
+    pub fn compute_embedding(&self, image: &Tensor) -> Result<Tensor, FaceAuthError> {
+        Ok(self.run(image)?)
+    }
+
+    fn run(&self, image: &Tensor) -> Result<Tensor> {
+        let image = if image.rank() == 3 { image.unsqueeze(0)? } else { image.clone() };
+        let dims = image.dims().to_vec();
+        if dims != [1, 3, ONNX_INPUT_SIZE, ONNX_INPUT_SIZE] {
+            anyhow::bail!("ONNX model expects a (1, 3, {0}, {0}) image, got {1:?}", ONNX_INPUT_SIZE, dims);
+        }
+        let data = image.flatten_all()?.to_vec1::<f32>()?;
+        let input = tract_onnx::prelude::Tensor::from_shape(&dims, &data)?;
+        let outputs = self.plan.run(tvec!(input.into()))?;
+        let embedding: Vec<f32> = outputs[0].to_array_view::<f32>()?.iter().copied().collect();
+        let len = embedding.len();
+        Ok(Tensor::from_vec(embedding, (1, len), &Device::Cpu)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ex01_image_processing_solution::imagenet::load_image224;
+
+    // models/tiny_gap.onnx is GlobalAveragePool followed by Flatten: a 3-d "embedding" holding
+    // the mean of each colour channel, small enough to check by hand
+    #[test]
+    fn tiny_onnx_model_produces_channel_means() -> Result<()> {
+        let model = OnnxModel::load("models/tiny_gap.onnx")?;
+        let image = load_image224("../../../app/test_images/tom.png")?;
+        let embedding = model.compute_embedding(&image)?;
+        assert_eq!(embedding.dims(), &[1, 3]);
+
+        let expected = image.flatten_from(1)?.mean(1)?.to_vec1::<f32>()?;
+        for (got, want) in embedding.flatten_all()?.to_vec1::<f32>()?.iter().zip(&expected) {
+            assert!((got - want).abs() < 1e-4, "Channel mean {} vs {}", got, want);
+        }
+        assert!(model.compute_embedding(&Tensor::zeros((3, 112, 112), candle_core::DType::F32, &Device::Cpu)?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn missing_onnx_file_is_a_model_load_error() {
+        assert!(matches!(OnnxModel::load("models/does_not_exist.onnx"), Err(FaceAuthError::ModelLoad(_))));
+    }
+}
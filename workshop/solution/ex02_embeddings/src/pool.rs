@@ -0,0 +1,126 @@
+use candle_nn::Func;
+use face_auth_error::FaceAuthError;
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex, PoisonError};
+
+use crate::build_model;
+
+/// A fixed set of ready models shared between threads. `acquire` blocks while every model is
+/// in use, so at most `size()` inferences run at once no matter how many callers there are.
+pub struct ModelPool {
+    idle: Mutex<Vec<Func<'static>>>,
+    returned: Condvar,
+    size: usize,
+}
+
+impl ModelPool {
+    /// Build `size` models with `build_model`; they share the downloaded weights file.
+    pub fn new(size: usize) -> Result<Self, FaceAuthError> {
+        let models = (0..size).map(|_| build_model()).collect::<Result<Vec<_>, _>>()?;
+        Self::from_models(models)
+    }
+
+    /// Pool already-built models, e.g. ones loaded onto a GPU or from a local file.
+    pub fn from_models(models: Vec<Func<'static>>) -> Result<Self, FaceAuthError> {
+        if models.is_empty() {
+            return Err(anyhow::anyhow!("A model pool needs at least one model").into());
+        }
+        Ok(ModelPool {
+            size: models.len(),
+            idle: Mutex::new(models),
+            returned: Condvar::new(),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Wait for an idle model. It goes back to the pool when the guard is dropped.
+    pub fn acquire(&self) -> ModelGuard<'_> {
+        // A panicking holder cannot leave the Vec half-updated, so a poisoned lock is still usable
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            if let Some(model) = idle.pop() {
+                return ModelGuard { pool: self, model: Some(model) };
+            }
+            idle = self.returned.wait(idle).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+/// A model borrowed from a `ModelPool`; dereferences to the `Func`.
+pub struct ModelGuard<'a> {
+    pool: &'a ModelPool,
+    model: Option<Func<'static>>,
+}
+
+impl Deref for ModelGuard<'_> {
+    type Target = Func<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        self.model.as_ref().expect("model is only taken on drop")
+    }
+}
+
+impl Drop for ModelGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(model) = self.model.take() {
+            self.pool.idle.lock().unwrap_or_else(PoisonError::into_inner).push(model);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute_embedding;
+    use anyhow::Result;
+    use candle_core::{DType, Device, Tensor};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn pool_caps_concurrent_inferences() -> Result<()> {
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        // A stand-in model that tracks how many forward passes overlap
+        let model = |active: Arc<AtomicUsize>, peak: Arc<AtomicUsize>| {
+            Func::new(move |xs| {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(20));
+                active.fetch_sub(1, Ordering::SeqCst);
+                xs.flatten_from(1)
+            })
+        };
+        let models = (0..2).map(|_| model(Arc::clone(&active), Arc::clone(&peak))).collect();
+        let pool = ModelPool::from_models(models)?;
+        assert_eq!(pool.size(), 2);
+
+        let embeddings = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let pool = &pool;
+                    scope.spawn(move || -> Result<Tensor> {
+                        let image = Tensor::full(i as f32, (3, 4, 4), &Device::Cpu)?;
+                        Ok(compute_embedding(&pool.acquire(), &image)?)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().expect("worker panicked")).collect::<Result<Vec<_>>>()
+        })?;
+
+        let peak = peak.load(Ordering::SeqCst);
+        assert!((1..=2).contains(&peak), "{} forward passes overlapped with a pool of two", peak);
+        for (i, embedding) in embeddings.iter().enumerate() {
+            assert_eq!(embedding.dims(), &[1, 48]);
+            assert_eq!(embedding.to_dtype(DType::F32)?.mean_all()?.to_vec0::<f32>()?, i as f32);
+        }
+        assert_eq!(pool.idle.lock().unwrap().len(), 2, "Every model should be back in the pool");
+        assert!(ModelPool::from_models(Vec::new()).is_err());
+        Ok(())
+    }
+}
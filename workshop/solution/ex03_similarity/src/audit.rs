@@ -0,0 +1,88 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// One verification or identification attempt, as written to an audit trail.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthAttempt {
+    pub at: DateTime<Utc>,
+    /// Best candidate compared against; `None` for 1:1 checks without an identity or an empty gallery.
+    pub candidate_id: Option<String>,
+    pub candidate_name: Option<String>,
+    /// Similarity to the candidate, `None` when nothing was compared.
+    pub similarity: Option<f32>,
+    pub threshold: f32,
+    pub passed: bool,
+}
+
+/// Sink for authentication attempts. Implementations must be append-only.
+pub trait AuditLog {
+    fn record_attempt(&self, attempt: AuthAttempt) -> Result<()>;
+}
+
+/// Appends one JSON object per line to a file, creating it if needed.
+pub struct JsonlAuditLog {
+    file: Mutex<File>,
+}
+
+impl JsonlAuditLog {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+        Ok(JsonlAuditLog { file: Mutex::new(file) })
+    }
+}
+
+impl AuditLog for JsonlAuditLog {
+    // The line is written with a single call so concurrent attempts never interleave
+    fn record_attempt(&self, attempt: AuthAttempt) -> Result<()> {
+        let mut line = serde_json::to_string(&attempt)?;
+        line.push('\n');
+        let mut file = self.file.lock().map_err(|_| anyhow::anyhow!("Audit log lock poisoned"))?;
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verification::verify_audited;
+    use candle_core::{Device, Tensor};
+
+    #[test]
+    fn verifications_are_appended_as_json_lines() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("face_auth_audit_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let a = Tensor::from_vec(vec![1f32, 0.0], (1, 2), &Device::Cpu)?;
+        let b = Tensor::from_vec(vec![1f32, 1.0], (1, 2), &Device::Cpu)?;
+        {
+            let log = JsonlAuditLog::open(&path)?;
+            assert!(verify_audited(&a, &b, 0.5, Some(&log))?.is_match);
+        }
+        // Reopening appends rather than truncating
+        let log = JsonlAuditLog::open(&path)?;
+        assert!(!verify_audited(&a, &b, 0.9, Some(&log))?.is_match);
+        verify_audited(&a, &b, 0.9, None)?;
+
+        let contents = std::fs::read_to_string(&path)?;
+        let _ = std::fs::remove_file(&path);
+        let attempts: Vec<AuthAttempt> = contents.lines().map(serde_json::from_str).collect::<Result<_, _>>()?;
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts[0].passed && !attempts[1].passed);
+        assert_eq!(attempts[1].threshold, 0.9);
+        assert!((attempts[0].similarity.unwrap() - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert!(attempts[0].at <= attempts[1].at);
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use crate::verification::DEFAULT_MATCH_THRESHOLD;
+use anyhow::Result;
+
+const DEFAULT_SLOPE: f32 = 20.0;
+// L2 penalty on the slope, keeps the fit finite when the pairs are perfectly separable
+const RIDGE: f64 = 1e-3;
+const MAX_ITERATIONS: usize = 100;
+
+/// Logistic mapping `p = 1 / (1 + exp(-(slope * sim + bias)))` from cosine similarity to a
+/// same-person probability.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalibrationParams {
+    pub slope: f32,
+    pub bias: f32,
+}
+
+impl Default for CalibrationParams {
+    /// Uncalibrated fallback: 50% exactly at `DEFAULT_MATCH_THRESHOLD`.
+    fn default() -> Self {
+        CalibrationParams {
+            slope: DEFAULT_SLOPE,
+            bias: -DEFAULT_SLOPE * DEFAULT_MATCH_THRESHOLD,
+        }
+    }
+}
+
+impl CalibrationParams {
+    pub fn probability(&self, similarity: f32) -> f32 {
+        1.0 / (1.0 + (-(self.slope * similarity + self.bias)).exp())
+    }
+}
+
+/// Map a similarity to a probability in [0, 1] using the default parameters.
+pub fn to_probability(similarity: f32) -> f32 {
+    CalibrationParams::default().probability(similarity)
+}
+
+/// Fit slope and bias by logistic regression (Newton's method) on labelled
+/// `(similarity, same_person)` pairs. Both labels must be present.
+pub fn fit(pairs: &[(f32, bool)]) -> Result<CalibrationParams> {
+    if !pairs.iter().any(|(_, same)| *same) || !pairs.iter().any(|(_, same)| !*same) {
+        anyhow::bail!("Calibration needs both same-person and different-person pairs");
+    }
+
+    let (mut slope, mut bias) = (0f64, 0f64);
+    for _ in 0..MAX_ITERATIONS {
+        // Gradient and Hessian of the regularized negative log-likelihood
+        let (mut g_slope, mut g_bias) = (RIDGE * slope, 0.0);
+        let (mut h_ss, mut h_sb, mut h_bb) = (RIDGE, 0.0, 0.0);
+        for &(similarity, same) in pairs {
+            let x = similarity as f64;
+            let p = 1.0 / (1.0 + (-(slope * x + bias)).exp());
+            let residual = p - if same { 1.0 } else { 0.0 };
+            let weight = (p * (1.0 - p)).max(1e-12);
+            g_slope += residual * x;
+            g_bias += residual;
+            h_ss += weight * x * x;
+            h_sb += weight * x;
+            h_bb += weight;
+        }
+
+        let det = h_ss * h_bb - h_sb * h_sb;
+        if det.abs() < 1e-18 {
+            break;
+        }
+        let step_slope = (h_bb * g_slope - h_sb * g_bias) / det;
+        let step_bias = (h_ss * g_bias - h_sb * g_slope) / det;
+        slope -= step_slope;
+        bias -= step_bias;
+        if step_slope.abs() < 1e-9 && step_bias.abs() < 1e-9 {
+            break;
+        }
+    }
+
+    Ok(CalibrationParams {
+        slope: slope as f32,
+        bias: bias as f32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fitted_probability_is_monotonic() -> Result<()> {
+        let pairs = [
+            (0.95, true),
+            (0.88, true),
+            (0.81, true),
+            (0.74, true),
+            (0.66, false),
+            (0.72, false),
+            (0.45, false),
+            (0.30, false),
+            (0.12, false),
+            (0.78, true),
+        ];
+        let params = fit(&pairs)?;
+        assert!(params.slope > 0.0, "slope should be positive: {:?}", params);
+
+        let probabilities: Vec<f32> = (-10..=10).map(|i| params.probability(i as f32 / 10.0)).collect();
+        assert!(probabilities.windows(2).all(|w| w[0] <= w[1]));
+        assert!(probabilities.iter().all(|p| (0.0..=1.0).contains(p)));
+        assert!(params.probability(0.9) > 0.5);
+        assert!(params.probability(0.3) < 0.5);
+        Ok(())
+    }
+
+    #[test]
+    fn fit_handles_separable_and_one_sided_pairs() -> Result<()> {
+        // Perfectly separable data still yields finite parameters
+        let params = fit(&[(0.9, true), (0.8, true), (0.2, false), (0.1, false)])?;
+        assert!(params.slope.is_finite() && params.bias.is_finite());
+        assert!(params.probability(0.85) > params.probability(0.15));
+
+        assert!(fit(&[]).is_err());
+        assert!(fit(&[(0.9, true), (0.8, true)]).is_err());
+
+        assert!((to_probability(DEFAULT_MATCH_THRESHOLD) - 0.5).abs() < 1e-6);
+        Ok(())
+    }
+}
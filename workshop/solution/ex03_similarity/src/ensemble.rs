@@ -0,0 +1,123 @@
+use crate::{cosine_similarity, cosine_similarity_vec, normalize_l2_vec};
+use anyhow::Result;
+use candle_core::Tensor;
+use candle_nn::Func;
+use ex02_embeddings_solution::compute_embedding;
+
+/// How an `Ensemble` turns one embedding per model into a single score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fusion {
+    /// Mean of the per-model cosine similarities.
+    #[default]
+    AverageCosine,
+    /// Cosine similarity of the L2-normalized embeddings concatenated into one vector. For two
+    /// images this equals the average (up to rounding), but the fused vector can be stored and
+    /// searched like a single-model embedding, see `Ensemble::fused_embedding`.
+    Concatenate,
+}
+
+/// Several embedding models scored together, e.g. two different backbones, which is a cheap
+/// way to cut false accepts. Models may have different embedding sizes.
+pub struct Ensemble<'a> {
+    models: Vec<Func<'a>>,
+    fusion: Fusion,
+}
+
+impl<'a> Ensemble<'a> {
+    /// An ensemble needs at least one model.
+    pub fn new(models: Vec<Func<'a>>, fusion: Fusion) -> Result<Self> {
+        if models.is_empty() {
+            anyhow::bail!("An ensemble needs at least one model");
+        }
+        Ok(Ensemble { models, fusion })
+    }
+
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    pub fn fusion(&self) -> Fusion {
+        self.fusion
+    }
+
+    /// One `compute_embedding` result per model, in model order.
+    pub fn embed(&self, image: &Tensor) -> Result<Vec<Tensor>> {
+        self.models.iter().map(|model| Ok(compute_embedding(model, image)?)).collect()
+    }
+
+    /// The per-model embeddings L2-normalized and concatenated, the vector `Fusion::Concatenate`
+    /// compares.
+    pub fn fused_embedding(&self, image: &Tensor) -> Result<Vec<f32>> {
+        concatenate(&self.embed(image)?)
+    }
+
+    /// Fused similarity of two images: each is embedded by every model, then combined by `fusion`.
+    pub fn score(&self, image_a: &Tensor, image_b: &Tensor) -> Result<f32> {
+        self.score_embeddings(&self.embed(image_a)?, &self.embed(image_b)?)
+    }
+
+    /// `score` for embeddings already returned by `embed`.
+    pub fn score_embeddings(&self, emb_a: &[Tensor], emb_b: &[Tensor]) -> Result<f32> {
+        if emb_a.len() != self.models.len() || emb_b.len() != self.models.len() {
+            anyhow::bail!(
+                "Expected one embedding per model ({}), got {} and {}",
+                self.models.len(),
+                emb_a.len(),
+                emb_b.len()
+            );
+        }
+        match self.fusion {
+            Fusion::AverageCosine => {
+                let mut total = 0.0;
+                for (a, b) in emb_a.iter().zip(emb_b) {
+                    total += cosine_similarity(a, b)?;
+                }
+                Ok(total / self.models.len() as f32)
+            }
+            Fusion::Concatenate => Ok(cosine_similarity_vec(&concatenate(emb_a)?, &concatenate(emb_b)?)?),
+        }
+    }
+}
+
+fn concatenate(embeddings: &[Tensor]) -> Result<Vec<f32>> {
+    let mut fused = Vec::new();
+    for embedding in embeddings {
+        fused.extend(normalize_l2_vec(&embedding.flatten_all()?.to_vec1::<f32>()?));
+    }
+    Ok(fused)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ex01_image_processing_solution::imagenet::load_image224;
+
+    // Stand-in for a real backbone: the flattened pixels are the embedding
+    fn pixel_model() -> Func<'static> {
+        Func::new(|xs| xs.flatten_from(1))
+    }
+
+    #[test]
+    fn same_model_twice_scores_like_one_model() -> Result<()> {
+        let brad = load_image224("../../../app/test_images/brad1.png")?;
+        let tom = load_image224("../../../app/test_images/tom.png")?;
+        let single = cosine_similarity(&compute_embedding(&pixel_model(), &brad)?, &compute_embedding(&pixel_model(), &tom)?)?;
+
+        for fusion in [Fusion::AverageCosine, Fusion::Concatenate] {
+            let ensemble = Ensemble::new(vec![pixel_model(), pixel_model()], fusion)?;
+            let fused = ensemble.score(&brad, &tom)?;
+            assert!((fused - single).abs() < 1e-5, "{:?}: ensemble {} vs single {}", fusion, fused, single);
+        }
+
+        let ensemble = Ensemble::new(vec![pixel_model(), pixel_model()], Fusion::default())?;
+        assert_eq!(ensemble.fused_embedding(&brad)?.len(), 2 * 3 * 224 * 224);
+        let one = ensemble.embed(&brad)?;
+        assert!(ensemble.score_embeddings(&one[..1], &one).is_err());
+        assert!(Ensemble::new(Vec::new(), Fusion::Concatenate).is_err());
+        Ok(())
+    }
+}
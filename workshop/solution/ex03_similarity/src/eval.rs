@@ -0,0 +1,250 @@
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fmt;
+
+/// One operating point: pairs scoring at or above `threshold` are predicted to match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RocPoint {
+    pub threshold: f32,
+    pub tpr: f32,
+    pub fpr: f32,
+    pub accuracy: f32,
+}
+
+/// ROC curve from the strictest cutoff (nothing accepted) down to the loosest (everything accepted).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RocCurve {
+    pub points: Vec<RocPoint>,
+}
+
+impl RocCurve {
+    /// Area under the curve by the trapezoidal rule.
+    pub fn auc(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|w| (w[1].fpr - w[0].fpr) * (w[1].tpr + w[0].tpr) / 2.0)
+            .sum()
+    }
+}
+
+/// Build the ROC curve for labelled `(similarity, same_person)` scores, with one point per distinct score.
+pub fn roc(scores: &[(f32, bool)]) -> RocCurve {
+    let positives = scores.iter().filter(|(_, same)| *same).count();
+    let negatives = scores.len() - positives;
+    let rate = |count: usize, total: usize| if total == 0 { 0.0 } else { count as f32 / total as f32 };
+
+    let mut sorted = scores.to_vec();
+    sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut points = vec![RocPoint {
+        threshold: f32::INFINITY,
+        tpr: 0.0,
+        fpr: 0.0,
+        accuracy: rate(negatives, scores.len()),
+    }];
+    let (mut tp, mut fp) = (0, 0);
+    for (i, &(score, same)) in sorted.iter().enumerate() {
+        if same { tp += 1 } else { fp += 1 }
+        // Emit a point only once every pair with this score has been accepted
+        if sorted.get(i + 1).is_some_and(|next| next.0 == score) {
+            continue;
+        }
+        points.push(RocPoint {
+            threshold: score,
+            tpr: rate(tp, positives),
+            fpr: rate(fp, negatives),
+            accuracy: rate(tp + negatives - fp, scores.len()),
+        });
+    }
+    RocCurve { points }
+}
+
+/// Threshold maximizing Youden's J (TPR - FPR), ties broken by accuracy, together with the
+/// accuracy it achieves. Compatible with `verification::is_match`.
+pub fn best_threshold(scores: &[(f32, bool)]) -> Result<(f32, f32)> {
+    if !scores.iter().any(|(_, same)| *same) || !scores.iter().any(|(_, same)| !*same) {
+        anyhow::bail!("Threshold selection needs both same-person and different-person pairs");
+    }
+    let curve = roc(scores);
+    let best = curve
+        .points
+        .iter()
+        .skip(1)
+        .max_by(|a, b| {
+            (a.tpr - a.fpr)
+                .total_cmp(&(b.tpr - b.fpr))
+                .then_with(|| a.accuracy.total_cmp(&b.accuracy))
+        })
+        .expect("non-empty scores produce at least one finite point");
+    Ok((best.threshold, best.accuracy))
+}
+
+/// Aggregate outcome of identifying a labelled set of queries against a gallery.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdentificationStats {
+    pub total: usize,
+    /// Queries identified as their true identity.
+    pub true_accepts: usize,
+    /// Queries identified as someone else.
+    pub false_accepts: usize,
+    /// Queries with no identification (`None`).
+    pub false_rejects: usize,
+    /// `true_accepts / total`, 0 for an empty run.
+    pub accuracy: f32,
+}
+
+impl IdentificationStats {
+    /// Compare each prediction with the true identity at the same position.
+    /// Both slices must have the same length.
+    pub fn from_results(predicted: &[Option<String>], truth: &[String]) -> Result<Self> {
+        if predicted.len() != truth.len() {
+            anyhow::bail!("{} predictions for {} ground-truth labels", predicted.len(), truth.len());
+        }
+        let (mut true_accepts, mut false_accepts, mut false_rejects) = (0, 0, 0);
+        for (prediction, expected) in predicted.iter().zip(truth) {
+            match prediction {
+                Some(name) if name == expected => true_accepts += 1,
+                Some(_) => false_accepts += 1,
+                None => false_rejects += 1,
+            }
+        }
+        let total = truth.len();
+        Ok(IdentificationStats {
+            total,
+            true_accepts,
+            false_accepts,
+            false_rejects,
+            accuracy: if total == 0 { 0.0 } else { true_accepts as f32 / total as f32 },
+        })
+    }
+}
+
+impl fmt::Display for IdentificationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} identified correctly ({:.1}%), {} false accepts, {} false rejects",
+            self.true_accepts,
+            self.total,
+            self.accuracy * 100.0,
+            self.false_accepts,
+            self.false_rejects
+        )
+    }
+}
+
+/// Split `pairs` into `k` cross-validation folds as `(train, test)` index lists, for fitting a
+/// threshold on one part and measuring it on the other. Every index is in exactly one test fold,
+/// and each fold's train list holds all the others. Folds are stratified by label, so each gets
+/// its share of same-person pairs, and their sizes differ by at most one. The same `seed` always
+/// gives the same splits. `k` is clamped to `1..=pairs.len()`; with `k = 1` the lone fold tests
+/// on everything and trains on nothing.
+pub fn kfold(pairs: &[(f32, bool)], k: usize, seed: u64) -> Vec<(Vec<usize>, Vec<usize>)> {
+    if pairs.is_empty() {
+        return Vec::new();
+    }
+    let k = k.clamp(1, pairs.len());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (mut same, mut different): (Vec<usize>, Vec<usize>) = (0..pairs.len()).partition(|&i| pairs[i].1);
+    same.shuffle(&mut rng);
+    different.shuffle(&mut rng);
+
+    // Deal the shuffled indices round-robin, one class after the other
+    let mut fold_of = vec![0; pairs.len()];
+    for (position, &index) in same.iter().chain(&different).enumerate() {
+        fold_of[index] = position % k;
+    }
+    (0..k)
+        .map(|fold| (0..pairs.len()).partition(|&i| fold_of[i] != fold))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verification::is_match;
+
+    #[test]
+    fn separable_scores_give_clean_threshold() -> Result<()> {
+        let scores = [
+            (0.95, true),
+            (0.91, true),
+            (0.84, true),
+            (0.80, true),
+            (0.52, false),
+            (0.47, false),
+            (0.30, false),
+            (0.12, false),
+            (-0.05, false),
+        ];
+        let (threshold, accuracy) = best_threshold(&scores)?;
+        assert_eq!(threshold, 0.80);
+        assert_eq!(accuracy, 1.0);
+        assert!(scores.iter().all(|&(score, same)| is_match(score, threshold) == same));
+
+        let curve = roc(&scores);
+        assert!((curve.auc() - 1.0).abs() < 1e-6);
+        let last = curve.points.last().expect("curve has points");
+        assert_eq!((last.tpr, last.fpr), (1.0, 1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn roc_groups_tied_scores_and_rejects_one_class() -> Result<()> {
+        let curve = roc(&[(0.9, true), (0.5, true), (0.5, false), (0.1, false)]);
+        let thresholds: Vec<f32> = curve.points.iter().map(|p| p.threshold).collect();
+        assert_eq!(thresholds, vec![f32::INFINITY, 0.9, 0.5, 0.1]);
+        assert_eq!((curve.points[2].tpr, curve.points[2].fpr), (1.0, 0.5));
+        assert!((curve.auc() - 0.875).abs() < 1e-6);
+
+        assert!(best_threshold(&[]).is_err());
+        assert!(best_threshold(&[(0.9, true)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn kfold_test_folds_partition_every_index() {
+        let pairs: Vec<(f32, bool)> = (0..23).map(|i| (i as f32 / 23.0, i % 3 == 0)).collect();
+        let folds = kfold(&pairs, 5, 7);
+        assert_eq!(folds.len(), 5);
+
+        let mut seen = vec![0; pairs.len()];
+        for (train, test) in &folds {
+            assert_eq!(train.len() + test.len(), pairs.len());
+            assert!(test.iter().all(|i| !train.contains(i)));
+            assert!((4..=5).contains(&test.len()), "Unbalanced fold of {}", test.len());
+            let positives = test.iter().filter(|&&i| pairs[i].1).count();
+            assert!((1..=2).contains(&positives), "Fold got {} of the 8 positives", positives);
+            for &i in test {
+                seen[i] += 1;
+            }
+        }
+        assert!(seen.iter().all(|&count| count == 1));
+
+        assert_eq!(kfold(&pairs, 5, 7), folds);
+        assert_ne!(kfold(&pairs, 5, 8), folds);
+        assert_eq!(kfold(&pairs[..3], 10, 7).len(), 3);
+        assert!(kfold(&[], 5, 7).is_empty());
+    }
+
+    #[test]
+    fn identification_stats_count_each_outcome() -> Result<()> {
+        let some = |name: &str| Some(name.to_string());
+        let predicted = [some("brad"), some("brad"), some("tom"), None, some("brad"), None];
+        let truth = ["brad", "brad", "brad", "tom", "tom", "tom"].map(String::from);
+
+        let stats = IdentificationStats::from_results(&predicted, &truth)?;
+        assert_eq!(
+            (stats.total, stats.true_accepts, stats.false_accepts, stats.false_rejects),
+            (6, 2, 2, 2)
+        );
+        assert!((stats.accuracy - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(stats.to_string(), "2/6 identified correctly (33.3%), 2 false accepts, 2 false rejects");
+
+        assert_eq!(IdentificationStats::from_results(&[], &[])?.accuracy, 0.0);
+        assert!(IdentificationStats::from_results(&predicted[..2], &truth).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{check_vec_dims, cosine_similarity_vec, normalize_l2_vec};
+use face_auth_error::FaceAuthError;
+
+/// How much one embedding dimension adds to a cosine similarity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DimensionContribution {
+    pub index: usize,
+    /// `a[index] * b[index]` after both vectors are L2-normalized. Summed over every dimension
+    /// this gives the cosine similarity, and a negative value pulls the score down.
+    pub contribution: f32,
+}
+
+/// Why two embeddings scored as they did, for debugging surprising matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchExplanation {
+    pub similarity: f32,
+    /// The dimensions that raise the similarity most, largest contribution first.
+    pub top_dimensions: Vec<DimensionContribution>,
+}
+
+/// Break the cosine similarity of `a` and `b` down into the `top_n` dimensions that contribute
+/// most to it. Ties go to the lower index. Fewer than `top_n` are returned for short vectors.
+pub fn explain_match(a: &[f32], b: &[f32], top_n: usize) -> Result<MatchExplanation, FaceAuthError> {
+    check_vec_dims(a, b)?;
+    let (a_unit, b_unit) = (normalize_l2_vec(a), normalize_l2_vec(b));
+    let mut contributions: Vec<DimensionContribution> = a_unit
+        .iter()
+        .zip(&b_unit)
+        .enumerate()
+        .map(|(index, (x, y))| DimensionContribution { index, contribution: x * y })
+        .collect();
+    contributions.sort_by(|p, q| q.contribution.total_cmp(&p.contribution).then(p.index.cmp(&q.index)));
+    contributions.truncate(top_n);
+    Ok(MatchExplanation {
+        similarity: cosine_similarity_vec(a, b)?,
+        top_dimensions: contributions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn spiked_dimension_is_reported_first() -> Result<()> {
+        let mut a = vec![0.1f32; 16];
+        let mut b = vec![0.1f32; 16];
+        a[11] = 5.0;
+        b[11] = 4.0;
+        a[3] = -2.0;
+        b[3] = 2.0;
+
+        let explanation = explain_match(&a, &b, 3)?;
+        assert_eq!(explanation.top_dimensions.len(), 3);
+        assert_eq!(explanation.top_dimensions[0].index, 11);
+        assert!(explanation.top_dimensions.iter().all(|d| d.index != 3), "A cancelling dimension does not raise the score");
+        assert!((explanation.similarity - cosine_similarity_vec(&a, &b)?).abs() < 1e-6);
+
+        // Every contribution together adds back up to the cosine
+        let all = explain_match(&a, &b, usize::MAX)?;
+        assert_eq!(all.top_dimensions.len(), 16);
+        let total: f32 = all.top_dimensions.iter().map(|d| d.contribution).sum();
+        assert!((total - all.similarity).abs() < 1e-5, "{} vs {}", total, all.similarity);
+        assert!(explain_match(&a, &b[..4], 3).is_err());
+        Ok(())
+    }
+}
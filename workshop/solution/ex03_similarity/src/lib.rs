@@ -1,17 +1,450 @@
+//! The slice math (`cosine_similarity_vec`, `euclidean_distance_vec`, `normalize_l2_vec`, ...)
+//! needs neither a filesystem nor threads, so with `default-features = false` this crate builds
+//! for `wasm32-unknown-unknown`. Everything else sits behind the default `native` feature.
+
+#[cfg(feature = "native")]
 use anyhow::Result;
+#[cfg(feature = "native")]
 use candle_core::{Tensor};
+use face_auth_error::FaceAuthError;
+#[cfg(feature = "native")]
+use rayon::prelude::*;
+use wide::f32x8;
+
+#[cfg(feature = "native")]
+pub mod audit;
+#[cfg(feature = "native")]
+pub mod calibrate;
+#[cfg(feature = "native")]
+pub mod ensemble;
+#[cfg(feature = "native")]
+pub mod eval;
+pub mod explain;
+#[cfg(feature = "native")]
+pub mod ratelimit;
+#[cfg(feature = "native")]
+pub mod verification;
 
+/// Norms below this value are treated as zero-magnitude.
+pub const NORM_EPSILON: f32 = 1e-12;
 
-/// Normalize tensor using L2 normalization
+/// Normalize tensor using L2 normalization.
+/// Rows whose L2 norm is below `NORM_EPSILON` (e.g. an all-zero embedding)
+/// are returned unchanged instead of being divided by zero into NaNs.
+#[cfg(feature = "native")]
 fn normalize_l2(v: &Tensor) -> Result<Tensor> {
-    Ok(v.broadcast_div(&v.sqr()?.sum_keepdim(1)?.sqrt()?)?)
+    let norm = v.sqr()?.sum_keepdim(1)?.sqrt()?;
+    let degenerate = norm.lt(NORM_EPSILON)?;
+    let norm = degenerate.where_cond(&norm.ones_like()?, &norm)?;
+    Ok(v.broadcast_div(&norm)?)
 }
 
+/// Cosine similarity between two embedding tensors, compared as flat vectors, so a `[1, dim]`
+/// embedding and a `[dim]` one are interchangeable. Different element counts yield `DimensionMismatch`.
+#[cfg(feature = "native")]
+pub fn cosine_similarity(emb_a: &Tensor, emb_b: &Tensor) -> Result<f32, FaceAuthError> {
+    check_tensor_dims(emb_a, emb_b)?;
+    Ok(tensor_similarity(emb_a, emb_b)?)
+}
 
-pub fn cosine_similarity(emb_a: &Tensor, emb_b: &Tensor) -> Result<f32> {
-    let emb_a = normalize_l2(emb_a)?;
-    let emb_b = normalize_l2(emb_b)?;
+#[cfg(feature = "native")]
+fn tensor_similarity(emb_a: &Tensor, emb_b: &Tensor) -> Result<f32> {
+    let emb_a = normalize_l2(&emb_a.flatten_all()?.unsqueeze(0)?)?;
+    let emb_b = normalize_l2(&emb_b.flatten_all()?.unsqueeze(0)?)?;
     let similarity = emb_a.matmul(&emb_b.transpose(0, 1)?)?;
     let similarity_value = similarity.squeeze(0)?.squeeze(0)?.to_vec0::<f32>()?;
     Ok(similarity_value)
-}
\ No newline at end of file
+}
+
+/// Cosine similarity between two plain embedding vectors, in [-1, 1].
+/// The dot product and both norms are accumulated in one pass, 8 lanes at a time; the result
+/// is clamped because rounding can land just outside the range (e.g. 1.0000001 for a == b).
+pub fn cosine_similarity_vec(a: &[f32], b: &[f32]) -> Result<f32, FaceAuthError> {
+    check_vec_dims(a, b)?;
+    let mut dot = f32x8::ZERO;
+    let mut sq_a = f32x8::ZERO;
+    let mut sq_b = f32x8::ZERO;
+    let chunks_a = a.chunks_exact(8);
+    let chunks_b = b.chunks_exact(8);
+    let (tail_a, tail_b) = (chunks_a.remainder(), chunks_b.remainder());
+    for (ca, cb) in chunks_a.zip(chunks_b) {
+        let va = f32x8::from(<[f32; 8]>::try_from(ca).expect("chunks_exact yields 8 elements"));
+        let vb = f32x8::from(<[f32; 8]>::try_from(cb).expect("chunks_exact yields 8 elements"));
+        dot = va.mul_add(vb, dot);
+        sq_a = va.mul_add(va, sq_a);
+        sq_b = vb.mul_add(vb, sq_b);
+    }
+
+    // Scalar fallback for lengths that are not a multiple of 8
+    let (mut dot, mut sq_a, mut sq_b) = (dot.reduce_add(), sq_a.reduce_add(), sq_b.reduce_add());
+    for (x, y) in tail_a.iter().zip(tail_b) {
+        dot += x * y;
+        sq_a += x * x;
+        sq_b += y * y;
+    }
+    Ok((dot / (norm_or_one(sq_a.sqrt()) * norm_or_one(sq_b.sqrt()))).clamp(-1.0, 1.0))
+}
+
+/// Cosine similarity of two vectors that are already L2-normalized, as a plain dot product.
+/// Skips both magnitude computations of `cosine_similarity_vec`, so it is only correct when
+/// the caller knows both operands have unit length (e.g. normalized at enrollment and query time).
+pub fn dot_similarity_vec(a: &[f32], b: &[f32]) -> Result<f32, FaceAuthError> {
+    check_vec_dims(a, b)?;
+    let mut acc = f32x8::ZERO;
+    let chunks_a = a.chunks_exact(8);
+    let chunks_b = b.chunks_exact(8);
+    let (tail_a, tail_b) = (chunks_a.remainder(), chunks_b.remainder());
+    for (ca, cb) in chunks_a.zip(chunks_b) {
+        let va = f32x8::from(<[f32; 8]>::try_from(ca).expect("chunks_exact yields 8 elements"));
+        let vb = f32x8::from(<[f32; 8]>::try_from(cb).expect("chunks_exact yields 8 elements"));
+        acc = va.mul_add(vb, acc);
+    }
+    let tail: f32 = tail_a.iter().zip(tail_b).map(|(x, y)| x * y).sum();
+    Ok((acc.reduce_add() + tail).clamp(-1.0, 1.0))
+}
+
+#[cfg(test)]
+fn cosine_similarity_vec_scalar(a: &[f32], b: &[f32]) -> Result<f32, FaceAuthError> {
+    check_vec_dims(a, b)?;
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    Ok(dot / (vec_norm(a) * vec_norm(b)))
+}
+
+/// Euclidean (L2) distance between two embeddings after L2 normalization.
+/// Smaller means more similar; the result lies in [0, 2].
+/// Like `cosine_similarity`, tensors are compared as flat vectors and different element counts
+/// yield `DimensionMismatch`.
+#[cfg(feature = "native")]
+pub fn euclidean_distance(emb_a: &Tensor, emb_b: &Tensor) -> Result<f32, FaceAuthError> {
+    check_tensor_dims(emb_a, emb_b)?;
+    Ok(tensor_distance(emb_a, emb_b)?)
+}
+
+#[cfg(feature = "native")]
+fn tensor_distance(emb_a: &Tensor, emb_b: &Tensor) -> Result<f32> {
+    let emb_a = normalize_l2(&emb_a.flatten_all()?.unsqueeze(0)?)?;
+    let emb_b = normalize_l2(&emb_b.flatten_all()?.unsqueeze(0)?)?;
+    let distance = (emb_a - emb_b)?.sqr()?.sum_all()?.sqrt()?.to_vec0::<f32>()?;
+    Ok(distance)
+}
+
+/// Euclidean (L2) distance between two plain embedding vectors after L2 normalization.
+pub fn euclidean_distance_vec(a: &[f32], b: &[f32]) -> Result<f32, FaceAuthError> {
+    check_vec_dims(a, b)?;
+    let norm_a = vec_norm(a);
+    let norm_b = vec_norm(b);
+    let distance = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| (x / norm_a - y / norm_b).powi(2))
+        .sum::<f32>()
+        .sqrt();
+    Ok(distance)
+}
+
+/// Angle between two plain embedding vectors, scaled to [0, 1]: 0 for the same direction,
+/// 0.5 for orthogonal, 1 for opposite. Unlike cosine similarity it is linear in the angle.
+/// `cosine_similarity_vec` is clamped to [-1, 1], so `acos` never returns NaN.
+pub fn angular_distance(a: &[f32], b: &[f32]) -> Result<f32, FaceAuthError> {
+    Ok(cosine_similarity_vec(a, b)?.acos() / std::f32::consts::PI)
+}
+
+/// Comparison function used to rank embeddings, selectable at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Metric {
+    /// Cosine similarity, in [-1, 1]; higher is more similar.
+    #[default]
+    Cosine,
+    /// Euclidean distance between the L2-normalized vectors, in [0, 2]; lower is more similar.
+    Euclidean,
+    /// Raw dot product; higher is more similar. Equals cosine for unit-length embeddings.
+    Dot,
+}
+
+impl Metric {
+    /// Whether larger scores mean more similar (false only for distances).
+    pub fn higher_is_better(self) -> bool {
+        !matches!(self, Metric::Euclidean)
+    }
+}
+
+/// Score two plain embedding vectors with the chosen metric.
+pub fn score(metric: Metric, a: &[f32], b: &[f32]) -> Result<f32, FaceAuthError> {
+    match metric {
+        Metric::Cosine => cosine_similarity_vec(a, b),
+        Metric::Euclidean => euclidean_distance_vec(a, b),
+        Metric::Dot => {
+            check_vec_dims(a, b)?;
+            Ok(dot(a, b))
+        }
+    }
+}
+
+/// All pairwise cosine similarities: row `i` holds `queries[i]` scored against every gallery
+/// embedding. Every vector must have the same length, which is checked before any scoring.
+pub fn similarity_matrix(queries: &[Vec<f32>], gallery: &[Vec<f32>]) -> Result<Vec<Vec<f32>>, FaceAuthError> {
+    let Some(reference) = queries.first().or(gallery.first()) else {
+        return Ok(Vec::new());
+    };
+    for v in queries.iter().chain(gallery) {
+        check_vec_dims(reference, v)?;
+    }
+
+    // Normalize each gallery vector once; a row is then a run of dot products.
+    // Rows are scored in parallel natively and one after another on wasm.
+    #[cfg(feature = "native")]
+    let (gallery_iter, query_iter) = (gallery.par_iter(), queries.par_iter());
+    #[cfg(not(feature = "native"))]
+    let (gallery_iter, query_iter) = (gallery.iter(), queries.iter());
+    let gallery: Vec<Vec<f32>> = gallery_iter.map(|g| normalize_l2_vec(g)).collect();
+    Ok(query_iter
+        .map(|query| {
+            let query = normalize_l2_vec(query);
+            gallery.iter().map(|g| dot(&query, g)).collect()
+        })
+        .collect())
+}
+
+/// L2-normalize a plain vector; zero-magnitude input is returned unchanged.
+pub fn normalize_l2_vec(v: &[f32]) -> Vec<f32> {
+    let norm = vec_norm(v);
+    v.iter().map(|x| x / norm).collect()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// L2 norm of a vector, falling back to 1.0 for zero-magnitude input
+/// so that it passes through unchanged, matching `normalize_l2`.
+fn vec_norm(v: &[f32]) -> f32 {
+    norm_or_one(v.iter().map(|x| x * x).sum::<f32>().sqrt())
+}
+
+fn norm_or_one(norm: f32) -> f32 {
+    if norm < NORM_EPSILON { 1.0 } else { norm }
+}
+
+#[cfg(feature = "native")]
+fn check_tensor_dims(a: &Tensor, b: &Tensor) -> Result<(), FaceAuthError> {
+    if a.elem_count() != b.elem_count() {
+        return Err(FaceAuthError::DimensionMismatch {
+            expected: a.elem_count(),
+            actual: b.elem_count(),
+        });
+    }
+    Ok(())
+}
+
+fn check_vec_dims(a: &[f32], b: &[f32]) -> Result<(), FaceAuthError> {
+    if a.is_empty() || b.is_empty() {
+        return Err(FaceAuthError::EmptyInput);
+    }
+    if a.len() != b.len() {
+        return Err(FaceAuthError::DimensionMismatch {
+            expected: a.len(),
+            actual: b.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    #[cfg(feature = "native")]
+    use {
+        candle_core::{Device, Tensor},
+        candle_nn::Func,
+        ex01_image_processing_solution::image_with_std_mean,
+        ex02_embeddings_solution::{build_model, compute_embedding},
+    };
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn same_person_smaller_distance() -> Result<()> {
+        let reader1 = image::ImageReader::open("../../../app/test_images/brad1.png")?;
+        let image1 = reader1.decode()?;
+        let reader2 = image::ImageReader::open("../../../app/test_images/brad2.png")?;
+        let image2 = reader2.decode()?;
+        let reader3 = image::ImageReader::open("../../../app/test_images/tom.png")?;
+        let image3 = reader3.decode()?;
+
+        let imagenet_mean: [f32; 3] = [0.485, 0.456, 0.406];
+        let imagenet_std: [f32; 3] = [0.229, 0.224, 0.225];
+
+        let img1 = image_with_std_mean(&image1, 224, &imagenet_mean, &imagenet_std)?;
+        let img2 = image_with_std_mean(&image2, 224, &imagenet_mean, &imagenet_std)?;
+        let img3 = image_with_std_mean(&image3, 224, &imagenet_mean, &imagenet_std)?;
+        let model: Func = build_model()?;
+        let e1 = compute_embedding(&model, &img1)?;
+        let e2 = compute_embedding(&model, &img2)?;
+        let e3 = compute_embedding(&model, &img3)?;
+        let d_same = euclidean_distance(&e1, &e2)?;
+        let d_diff = euclidean_distance(&e1, &e3)?;
+        assert!(d_same < d_diff);
+
+        let v1 = e1.squeeze(0)?.to_vec1::<f32>()?;
+        let v2 = e2.squeeze(0)?.to_vec1::<f32>()?;
+        let v3 = e3.squeeze(0)?.to_vec1::<f32>()?;
+        assert!(euclidean_distance_vec(&v1, &v2)? < euclidean_distance_vec(&v1, &v3)?);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn euclidean_distance_rejects_bad_inputs() -> Result<()> {
+        let a = Tensor::from_vec(vec![1f32, 0.0], (1, 2), &Device::Cpu)?;
+        let b = Tensor::from_vec(vec![1f32, 0.0, 0.0], (1, 3), &Device::Cpu)?;
+        assert!(matches!(
+            euclidean_distance(&a, &b),
+            Err(FaceAuthError::DimensionMismatch { expected: 2, actual: 3 })
+        ));
+        assert!(matches!(
+            cosine_similarity(&a, &b),
+            Err(FaceAuthError::DimensionMismatch { expected: 2, actual: 3 })
+        ));
+        // Only the element count matters: a `[1, 2]` embedding equals its flat `[2]` form
+        let flat = a.flatten_all()?;
+        assert!(euclidean_distance(&a, &flat)? < 1e-6);
+        assert!((cosine_similarity(&a, &flat)? - 1.0).abs() < 1e-6);
+
+        assert!(matches!(euclidean_distance_vec(&[], &[]), Err(FaceAuthError::EmptyInput)));
+        assert!(matches!(cosine_similarity_vec(&[1.0], &[]), Err(FaceAuthError::EmptyInput)));
+        assert!(matches!(
+            euclidean_distance_vec(&[1.0, 0.0], &[1.0]),
+            Err(FaceAuthError::DimensionMismatch { expected: 2, actual: 1 })
+        ));
+
+        let d = euclidean_distance_vec(&[3.0, 4.0], &[6.0, 8.0])?;
+        assert!(d.abs() < 1e-6, "Parallel vectors should have zero distance, got {}", d);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    fn normalize_l2_zero_vector_has_no_nan() -> Result<()> {
+        let v = Tensor::zeros((1, 4), candle_core::DType::F32, &Device::Cpu)?;
+        let normed = normalize_l2(&v)?;
+        let values = normed.flatten_all()?.to_vec1::<f32>()?;
+        assert!(values.iter().all(|x| !x.is_nan()), "normalize_l2 produced NaN: {:?}", values);
+        assert!(values.iter().all(|&x| x == 0.0));
+
+        let other = Tensor::from_vec(vec![1f32, 2.0, 3.0, 4.0], (1, 4), &Device::Cpu)?;
+        let sim = cosine_similarity(&v, &other)?;
+        assert!(!sim.is_nan(), "cosine_similarity produced NaN");
+        assert!(!cosine_similarity_vec(&[0.0; 4], &[1.0, 2.0, 3.0, 4.0])?.is_nan());
+        Ok(())
+    }
+
+    #[test]
+    fn simd_cosine_matches_scalar() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(42);
+        for len in [1, 7, 8, 9, 15, 512, 513, 519] {
+            for _ in 0..10 {
+                let a: Vec<f32> = (0..len).map(|_| rng.random_range(-1.0..1.0)).collect();
+                let b: Vec<f32> = (0..len).map(|_| rng.random_range(-1.0..1.0)).collect();
+                let simd = cosine_similarity_vec(&a, &b)?;
+                let scalar = cosine_similarity_vec_scalar(&a, &b)?;
+                assert!((simd - scalar).abs() < 1e-6, "len {}: simd {} vs scalar {}", len, simd, scalar);
+            }
+        }
+        assert!(cosine_similarity_vec(&[1.0; 9], &[1.0; 8]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn metric_scores_point_the_right_way() -> Result<()> {
+        let query = [1.0, 0.0];
+        let near = [2.0, 0.2];
+        let far = [0.0, 1.0];
+        for metric in [Metric::Cosine, Metric::Euclidean, Metric::Dot] {
+            let (s_near, s_far) = (score(metric, &query, &near)?, score(metric, &query, &far)?);
+            if metric.higher_is_better() {
+                assert!(s_near > s_far, "{:?}: {} vs {}", metric, s_near, s_far);
+            } else {
+                assert!(s_near < s_far, "{:?}: {} vs {}", metric, s_near, s_far);
+            }
+        }
+        assert_eq!(score(Metric::Dot, &query, &near)?, 2.0);
+        assert_eq!(Metric::default(), Metric::Cosine);
+        assert!(score(Metric::Dot, &[1.0], &[1.0, 2.0]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn similarity_matrix_of_a_set_with_itself_is_symmetric() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(11);
+        let set: Vec<Vec<f32>> = (0..6).map(|_| (0..64).map(|_| rng.random_range(-1.0..1.0)).collect()).collect();
+
+        let matrix = similarity_matrix(&set, &set)?;
+        assert_eq!(matrix.len(), set.len());
+        for i in 0..set.len() {
+            assert_eq!(matrix[i].len(), set.len());
+            assert!((matrix[i][i] - 1.0).abs() < 1e-5, "Diagonal entry {} was {}", i, matrix[i][i]);
+            for j in 0..set.len() {
+                assert!((matrix[i][j] - matrix[j][i]).abs() < 1e-6);
+                assert!((matrix[i][j] - cosine_similarity_vec(&set[i], &set[j])?).abs() < 1e-5);
+            }
+        }
+
+        let rectangular = similarity_matrix(&set[..2], &set)?;
+        assert_eq!((rectangular.len(), rectangular[0].len()), (2, 6));
+        assert!(similarity_matrix(&[], &set)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn similarity_matrix_rejects_mismatched_dimensions() {
+        let queries = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let gallery = vec![vec![1.0, 0.0], vec![1.0, 0.0, 0.0]];
+        assert!(matches!(
+            similarity_matrix(&queries, &gallery),
+            Err(FaceAuthError::DimensionMismatch { expected: 2, actual: 3 })
+        ));
+    }
+
+    #[test]
+    fn angular_distance_is_linear_in_angle() -> Result<()> {
+        let v = [0.3, -1.2, 0.7, 2.0, 0.1, -0.4, 0.9, 1.5, -0.8];
+        assert_eq!(angular_distance(&v, &v)?, 0.0);
+        assert!((angular_distance(&[1.0, 0.0], &[0.0, 3.0])? - 0.5).abs() < 1e-6);
+        assert!((angular_distance(&[1.0, 0.0], &[-2.0, 0.0])? - 1.0).abs() < 1e-6);
+
+        // Near-parallel inputs can round the cosine above 1.0
+        let scaled: Vec<f32> = v.iter().map(|x| x * 1.000_001).collect();
+        let d = angular_distance(&v, &scaled)?;
+        assert!(!d.is_nan() && (0.0..1e-3).contains(&d), "Got {}", d);
+        assert!(angular_distance(&[1.0], &[1.0, 0.0]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cosine_is_clamped_to_one() -> Result<()> {
+        let v = [729.0 / 997.0 - 0.5, 648.0 / 997.0 - 0.5, 567.0 / 997.0 - 0.5f32];
+        let sq: f32 = v.iter().map(|x| x * x).sum();
+        assert!(sq / (sq.sqrt() * sq.sqrt()) > 1.0, "Fixture no longer rounds above 1.0");
+
+        assert_eq!(cosine_similarity_vec(&v, &v)?, 1.0);
+        let opposite: Vec<f32> = v.iter().map(|x| -x).collect();
+        assert_eq!(cosine_similarity_vec(&v, &opposite)?, -1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn dot_on_normalized_matches_cosine_on_raw() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(5);
+        for len in [3, 8, 13, 512] {
+            let a: Vec<f32> = (0..len).map(|_| rng.random_range(-2.0..2.0)).collect();
+            let b: Vec<f32> = (0..len).map(|_| rng.random_range(-2.0..2.0)).collect();
+            let fast = dot_similarity_vec(&normalize_l2_vec(&a), &normalize_l2_vec(&b))?;
+            let cosine = cosine_similarity_vec(&a, &b)?;
+            assert!((fast - cosine).abs() < 1e-5, "len {}: dot {} vs cosine {}", len, fast, cosine);
+        }
+        assert!(dot_similarity_vec(&[1.0], &[1.0, 0.0]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use face_auth_error::FaceAuthError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// The bucket map is never swept while it holds fewer keys than this.
+const MIN_SWEEP_LEN: usize = 1024;
+
+/// Token-bucket limiter keyed by identity id or source address. Each key starts with `burst`
+/// tokens, every attempt spends one, and tokens come back at `rate_per_sec` up to `burst`.
+/// A bucket that has refilled to `burst` is the same as a missing one, so such buckets are
+/// dropped whenever the map doubles in size; memory follows the keys seen recently.
+pub struct RateLimiter {
+    rate_per_sec: f64,
+    burst: f64,
+    buckets: Mutex<Buckets>,
+}
+
+struct Buckets {
+    by_key: HashMap<String, Bucket>,
+    sweep_at: usize,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate_per_sec: f64, burst: u32) -> Self {
+        RateLimiter {
+            rate_per_sec: rate_per_sec.max(0.0),
+            burst: f64::from(burst),
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                sweep_at: MIN_SWEEP_LEN,
+            }),
+        }
+    }
+
+    /// Spend one token for `key`, returning `false` when its bucket is empty.
+    pub fn check(&self, key: &str) -> bool {
+        self.check_at(key, Instant::now())
+    }
+
+    /// `check`, as an error: `FaceAuthError::RateLimited` when `key` is over its limit.
+    pub fn acquire(&self, key: &str) -> Result<(), FaceAuthError> {
+        if self.check(key) {
+            Ok(())
+        } else {
+            Err(FaceAuthError::RateLimited(key.to_string()))
+        }
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if buckets.by_key.len() >= buckets.sweep_at {
+            buckets.by_key.retain(|_, bucket| self.refilled(bucket, now) < self.burst);
+            buckets.sweep_at = (buckets.by_key.len() * 2).max(MIN_SWEEP_LEN);
+        }
+        let bucket = buckets.by_key.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.rate_per_sec).min(self.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use std::time::Duration;
+
+    #[test]
+    fn burst_is_spent_then_refilled() -> Result<()> {
+        let limiter = RateLimiter::new(2.0, 3);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check_at("brad", start));
+        }
+        assert!(!limiter.check_at("brad", start));
+        assert!(!limiter.check_at("brad", start + Duration::from_millis(400)));
+        assert!(limiter.check_at("tom", start), "keys have independent buckets");
+
+        // 2 tokens/s: one token is back after half a second, but not two
+        let later = start + Duration::from_millis(900);
+        assert!(limiter.check_at("brad", later));
+        assert!(!limiter.check_at("brad", later));
+
+        // A long pause refills to `burst`, not beyond
+        let much_later = later + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.check_at("brad", much_later));
+        }
+        assert!(!limiter.check_at("brad", much_later));
+
+        let err = RateLimiter::new(1.0, 0).acquire("brad").unwrap_err();
+        assert!(matches!(err, FaceAuthError::RateLimited(key) if key == "brad"));
+        Ok(())
+    }
+
+    #[test]
+    fn idle_keys_are_evicted() -> Result<()> {
+        let limiter = RateLimiter::new(1.0, 1);
+        let start = Instant::now();
+        assert!(limiter.check_at("brad", start));
+
+        // One new source address per 100ms: each bucket is full again a second later
+        for i in 1..10 * MIN_SWEEP_LEN {
+            let now = start + Duration::from_millis(100 * i as u64);
+            assert!(limiter.check_at(&format!("10.0.{}.{}", i / 256, i % 256), now));
+        }
+        let kept = limiter.buckets.lock().unwrap().by_key.len();
+        assert!(kept <= MIN_SWEEP_LEN, "{kept} buckets kept");
+
+        // Evicting a full bucket changes nothing: it comes back full
+        let later = start + Duration::from_secs(3600);
+        assert!(limiter.check_at("brad", later));
+        assert!(!limiter.check_at("brad", later));
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use crate::audit::{AuditLog, AuthAttempt};
+use crate::cosine_similarity;
+use crate::ratelimit::RateLimiter;
+use anyhow::Result;
+use candle_core::Tensor;
+use face_auth_error::FaceAuthError;
+
+/// Default cosine similarity cutoff for deciding two faces belong to the same person.
+/// This is the login threshold used by the app: the brad1/brad2 fixtures score above it
+/// and brad1/tom below it (see `default_threshold_separates_fixtures`).
+pub const DEFAULT_MATCH_THRESHOLD: f32 = 0.7;
+
+/// Outcome of comparing two embeddings against a threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub similarity: f32,
+    pub threshold: f32,
+    pub is_match: bool,
+}
+
+/// A similarity at or above the threshold counts as a match. Every match decision follows this
+/// rule: `verify`, `identify` and `top_k_threshold` in the retrieval crate, and the app's login.
+pub fn is_match(similarity: f32, threshold: f32) -> bool {
+    similarity >= threshold
+}
+
+pub fn verify(emb_a: &Tensor, emb_b: &Tensor, threshold: f32) -> Result<Decision, FaceAuthError> {
+    verify_audited(emb_a, emb_b, threshold, None)
+}
+
+/// `verify`, recording the decision in `log` when one is given. A 1:1 check has no gallery
+/// candidate, so the attempt is logged without candidate id or name.
+pub fn verify_audited(
+    emb_a: &Tensor,
+    emb_b: &Tensor,
+    threshold: f32,
+    log: Option<&dyn AuditLog>,
+) -> Result<Decision, FaceAuthError> {
+    let similarity = cosine_similarity(emb_a, emb_b)?;
+    let decision = Decision {
+        similarity,
+        threshold,
+        is_match: is_match(similarity, threshold),
+    };
+    if let Some(log) = log {
+        log.record_attempt(AuthAttempt {
+            at: chrono::Utc::now(),
+            candidate_id: None,
+            candidate_name: None,
+            similarity: Some(similarity),
+            threshold,
+            passed: decision.is_match,
+        })?;
+    }
+    Ok(decision)
+}
+
+/// `verify_audited`, first spending one of `key`'s attempts on `limiter`. Over the limit it fails
+/// with `FaceAuthError::RateLimited` before comparing anything, so nothing is logged.
+pub fn verify_rate_limited(
+    emb_a: &Tensor,
+    emb_b: &Tensor,
+    threshold: f32,
+    limiter: &RateLimiter,
+    key: &str,
+    log: Option<&dyn AuditLog>,
+) -> Result<Decision, FaceAuthError> {
+    limiter.acquire(key)?;
+    verify_audited(emb_a, emb_b, threshold, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::Device;
+    use ex01_image_processing_solution::image_with_std_mean;
+    use ex02_embeddings_solution::{build_model, compute_embedding};
+
+    fn fixture_embedding(model: &candle_nn::Func, path: &str) -> Result<Tensor> {
+        let image = image::ImageReader::open(path)?.decode()?;
+        let imagenet_mean: [f32; 3] = [0.485, 0.456, 0.406];
+        let imagenet_std: [f32; 3] = [0.229, 0.224, 0.225];
+        let img = image_with_std_mean(&image, 224, &imagenet_mean, &imagenet_std)?;
+        Ok(compute_embedding(model, &img)?)
+    }
+
+    #[test]
+    fn default_threshold_separates_fixtures() -> Result<()> {
+        let model = build_model()?;
+        let brad1 = fixture_embedding(&model, "../../../app/test_images/brad1.png")?;
+        let brad2 = fixture_embedding(&model, "../../../app/test_images/brad2.png")?;
+        let tom = fixture_embedding(&model, "../../../app/test_images/tom.png")?;
+
+        let same = verify(&brad1, &brad2, DEFAULT_MATCH_THRESHOLD)?;
+        assert!(same.is_match, "brad1/brad2 should match: {:?}", same);
+        let different = verify(&brad1, &tom, DEFAULT_MATCH_THRESHOLD)?;
+        assert!(!different.is_match, "brad1/tom should not match: {:?}", different);
+        Ok(())
+    }
+
+    #[test]
+    fn decision_reports_similarity_and_threshold() -> Result<()> {
+        let a = Tensor::from_vec(vec![1f32, 0.0], (1, 2), &Device::Cpu)?;
+        let b = Tensor::from_vec(vec![1f32, 1.0], (1, 2), &Device::Cpu)?;
+        let decision = verify(&a, &b, 0.5)?;
+        assert!((decision.similarity - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(decision.threshold, 0.5);
+        assert!(decision.is_match);
+        assert!(!verify(&a, &b, 0.8)?.is_match);
+
+        assert!(is_match(0.7, 0.7));
+        assert!(!is_match(0.69, 0.7));
+        Ok(())
+    }
+
+    #[test]
+    fn rate_limited_verify_rejects_past_burst() -> Result<()> {
+        let a = Tensor::from_vec(vec![1f32, 0.0], (1, 2), &Device::Cpu)?;
+        let limiter = RateLimiter::new(0.0, 2);
+        assert!(verify_rate_limited(&a, &a, 0.5, &limiter, "brad", None)?.is_match);
+        assert!(verify_rate_limited(&a, &a, 0.5, &limiter, "brad", None)?.is_match);
+
+        let err = verify_rate_limited(&a, &a, 0.5, &limiter, "brad", None).unwrap_err();
+        assert!(matches!(err, FaceAuthError::RateLimited(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use super::{EmbeddingRecord, EmbeddingStorage, SharedStorage};
+use anyhow::Result;
+use std::future::Future;
+
+// Async mirror of `EmbeddingStorage` for use inside a tokio runtime
+pub trait AsyncEmbeddingStorage {
+    fn store_embedding(&mut self, record: EmbeddingRecord) -> impl Future<Output = Result<()>> + Send;
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> impl Future<Output = Result<()>> + Send;
+    fn get_embedding(&self, id: &str) -> impl Future<Output = Result<Option<EmbeddingRecord>>> + Send;
+    fn get_all_embeddings(&self) -> impl Future<Output = Result<Vec<EmbeddingRecord>>> + Send;
+    fn delete_embedding(&mut self, id: &str) -> impl Future<Output = Result<bool>> + Send;
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> impl Future<Output = Result<()>> + Send;
+}
+
+// Runs any sync backend on tokio's blocking thread pool, so file or database I/O
+// never stalls the async executor
+pub struct BlockingAdapter<S: EmbeddingStorage> {
+    inner: SharedStorage<S>,
+}
+
+impl<S: EmbeddingStorage> Clone for BlockingAdapter<S> {
+    fn clone(&self) -> Self {
+        BlockingAdapter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S: EmbeddingStorage + Send + Sync + 'static> BlockingAdapter<S> {
+    pub fn new(storage: S) -> Self {
+        BlockingAdapter {
+            inner: SharedStorage::new(storage),
+        }
+    }
+
+    async fn run<T, F>(&self, op: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(SharedStorage<S>) -> Result<T> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || op(inner)).await?
+    }
+}
+
+impl<S: EmbeddingStorage + Send + Sync + 'static> AsyncEmbeddingStorage for BlockingAdapter<S> {
+    async fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        self.run(move |mut inner| inner.store_embedding(record)).await
+    }
+
+    async fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        self.run(move |mut inner| inner.store_embeddings(records)).await
+    }
+
+    async fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+        let id = id.to_string();
+        self.run(move |inner| inner.get_embedding(&id)).await
+    }
+
+    async fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+        self.run(|inner| inner.get_all_embeddings()).await
+    }
+
+    async fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+        let id = id.to_string();
+        self.run(move |mut inner| inner.delete_embedding(&id)).await
+    }
+
+    async fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        self.run(move |mut inner| inner.update_embedding(record)).await
+    }
+}
@@ -0,0 +1,290 @@
+use super::{EmbeddingRecord, EmbeddingStorage, InMemoryStorage};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Version byte written at the start of every bincode file; bump it when the layout changes.
+/// Version 2 added `expires_at` and version 3 the per-record schema version; version 1 and 2
+/// files are still read.
+pub const BINCODE_FORMAT_VERSION: u8 = 3;
+
+// Record layout of format version 1, before `expires_at` existed
+#[derive(Deserialize)]
+struct RecordV1 {
+    id: String,
+    name: String,
+    embedding: Vec<f32>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    metadata: HashMap<String, String>,
+}
+
+impl From<RecordV1> for EmbeddingRecord {
+    fn from(record: RecordV1) -> Self {
+        EmbeddingRecord {
+            id: record.id,
+            name: record.name,
+            embedding: record.embedding,
+            created_at: record.created_at,
+            metadata: record.metadata,
+            expires_at: None,
+        }
+    }
+}
+
+// Record layout of format version 2, before records carried a schema version
+#[derive(Deserialize)]
+struct RecordV2 {
+    id: String,
+    name: String,
+    embedding: Vec<f32>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    metadata: HashMap<String, String>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<RecordV2> for EmbeddingRecord {
+    fn from(record: RecordV2) -> Self {
+        EmbeddingRecord {
+            id: record.id,
+            name: record.name,
+            embedding: record.embedding,
+            created_at: record.created_at,
+            metadata: record.metadata,
+            expires_at: record.expires_at,
+        }
+    }
+}
+
+// File-backed storage using a compact binary encoding instead of JSON. The file is one
+// version byte followed by the bincode-encoded records, rewritten atomically on every change.
+pub struct BincodeStorage {
+    file_path: PathBuf,
+    records: InMemoryStorage,
+}
+
+impl BincodeStorage {
+    pub fn new(file_path: impl Into<PathBuf>) -> Result<Self> {
+        let file_path = file_path.into();
+        let mut records = InMemoryStorage::new();
+        if file_path.exists() && fs::metadata(&file_path)?.len() > 0 {
+            records.store_embeddings(read_records(&file_path)?)?;
+        }
+        Ok(BincodeStorage { file_path, records })
+    }
+
+    fn save_data(&self) -> Result<()> {
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut tmp_path = self.file_path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = Path::new(&tmp_path);
+        {
+            let mut writer = BufWriter::new(File::create(tmp_path)?);
+            writer.write_all(&[BINCODE_FORMAT_VERSION])?;
+            bincode::serialize_into(&mut writer, &self.records.get_all_embeddings()?)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        fs::rename(tmp_path, &self.file_path)
+            .with_context(|| format!("Failed to move {} into place", self.file_path.display()))?;
+        Ok(())
+    }
+}
+
+fn read_records(path: &Path) -> Result<Vec<EmbeddingRecord>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut version = [0u8; 1];
+    reader.read_exact(&mut version)?;
+    let records = match version[0] {
+        1 => bincode::deserialize_from::<_, Vec<RecordV1>>(reader)
+            .map(|records| records.into_iter().map(EmbeddingRecord::from).collect()),
+        2 => bincode::deserialize_from::<_, Vec<RecordV2>>(reader)
+            .map(|records| records.into_iter().map(EmbeddingRecord::from).collect()),
+        BINCODE_FORMAT_VERSION => bincode::deserialize_from(reader),
+        other => anyhow::bail!(
+            "{} uses bincode format version {}, this build reads versions 1 to {}",
+            path.display(),
+            other,
+            BINCODE_FORMAT_VERSION
+        ),
+    };
+    records.with_context(|| format!("Failed to decode {}", path.display()))
+}
+
+impl EmbeddingStorage for BincodeStorage {
+    fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        self.records.store_embedding(record)?;
+        self.save_data()
+    }
+
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        self.records.store_embeddings(records)?;
+        self.save_data()
+    }
+
+    fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+        self.records.get_embedding(id)
+    }
+
+    fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+        self.records.get_all_embeddings()
+    }
+
+    fn iter_embeddings(&self) -> Box<dyn Iterator<Item = Result<EmbeddingRecord>> + '_> {
+        self.records.iter_embeddings()
+    }
+
+    fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+        let deleted = self.records.delete_embedding(id)?;
+        if deleted {
+            self.save_data()?;
+        }
+        Ok(deleted)
+    }
+
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        self.records.update_embedding(record)?;
+        self.save_data()
+    }
+
+    fn count(&self) -> Result<usize> {
+        self.records.count()
+    }
+
+    fn contains(&self, id: &str) -> Result<bool> {
+        self.records.contains(id)
+    }
+
+    fn dimension(&self) -> Result<Option<usize>> {
+        self.records.dimension()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{TempFileGuard, record};
+    use crate::LocalFileStorage;
+    use uuid::Uuid;
+
+    fn sorted(mut records: Vec<EmbeddingRecord>) -> Vec<EmbeddingRecord> {
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        records
+    }
+
+    #[test]
+    fn json_to_bincode_migration_keeps_every_record() -> Result<()> {
+        let json_path = format!("workshop_migrate_{}.json", Uuid::new_v4());
+        let bin_path = format!("workshop_migrate_{}.bin", Uuid::new_v4());
+        let _json_guard = TempFileGuard { path: json_path.clone() };
+        let _bin_guard = TempFileGuard { path: bin_path.clone() };
+
+        let mut json = LocalFileStorage::new(json_path.clone())?;
+        for i in 0..50 {
+            json.store_embedding(record(&format!("person_{i}"), vec![i as f32 * 0.1, -1.5, f32::MIN_POSITIVE]))?;
+        }
+
+        let mut migrated = BincodeStorage::new(&bin_path)?;
+        migrated.store_embeddings(json.get_all_embeddings()?)?;
+        drop(migrated);
+
+        let reopened = BincodeStorage::new(&bin_path)?;
+        assert_eq!(sorted(reopened.get_all_embeddings()?), sorted(json.get_all_embeddings()?));
+        assert!(
+            fs::metadata(&bin_path)?.len() < fs::metadata(&json_path)?.len(),
+            "Binary file should be smaller than the JSON one"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_format_version_is_rejected() -> Result<()> {
+        let path = format!("workshop_version_{}.bin", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+
+        let mut storage = BincodeStorage::new(&path)?;
+        storage.store_embedding(record("alice", vec![1.0, 0.0]))?;
+        let mut bytes = fs::read(&path)?;
+        assert_eq!(bytes[0], BINCODE_FORMAT_VERSION);
+        bytes[0] = BINCODE_FORMAT_VERSION + 1;
+        fs::write(&path, bytes)?;
+
+        let err = BincodeStorage::new(&path).err().expect("Newer format should be rejected");
+        let expected = format!("format version {}", BINCODE_FORMAT_VERSION + 1);
+        assert!(err.to_string().contains(&expected), "Unexpected error: {err}");
+        Ok(())
+    }
+
+    #[test]
+    fn version_one_files_are_still_read() -> Result<()> {
+        #[derive(serde::Serialize)]
+        struct LegacyRecord<'a> {
+            id: &'a str,
+            name: &'a str,
+            embedding: &'a [f32],
+            created_at: chrono::DateTime<chrono::Utc>,
+            metadata: &'a HashMap<String, String>,
+        }
+        let path = format!("workshop_legacy_{}.bin", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+
+        let alice = record("alice", vec![0.5, -0.5]);
+        let legacy = vec![LegacyRecord {
+            id: &alice.id,
+            name: &alice.name,
+            embedding: &alice.embedding,
+            created_at: alice.created_at,
+            metadata: &alice.metadata,
+        }];
+        let mut bytes = vec![1u8];
+        bytes.extend(bincode::serialize(&legacy)?);
+        fs::write(&path, bytes)?;
+
+        let storage = BincodeStorage::new(&path)?;
+        assert_eq!(storage.get_all_embeddings()?, vec![alice]);
+        Ok(())
+    }
+
+    #[test]
+    fn version_two_files_are_still_read() -> Result<()> {
+        #[derive(serde::Serialize)]
+        struct LegacyRecord<'a> {
+            id: &'a str,
+            name: &'a str,
+            embedding: &'a [f32],
+            created_at: chrono::DateTime<chrono::Utc>,
+            metadata: &'a HashMap<String, String>,
+            expires_at: Option<chrono::DateTime<chrono::Utc>>,
+        }
+        let path = format!("workshop_legacy_{}.bin", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+
+        let mut alice = record("alice", vec![0.5, -0.5]);
+        alice.expires_at = Some(alice.created_at + chrono::Duration::days(30));
+        let legacy = vec![LegacyRecord {
+            id: &alice.id,
+            name: &alice.name,
+            embedding: &alice.embedding,
+            created_at: alice.created_at,
+            metadata: &alice.metadata,
+            expires_at: alice.expires_at,
+        }];
+        let mut bytes = vec![2u8];
+        bytes.extend(bincode::serialize(&legacy)?);
+        fs::write(&path, bytes)?;
+
+        let mut storage = BincodeStorage::new(&path)?;
+        assert_eq!(storage.get_all_embeddings()?, vec![alice.clone()]);
+
+        // The next write upgrades the file to the current format
+        storage.store_embedding(record("bob", vec![0.0, 1.0]))?;
+        assert_eq!(fs::read(&path)?[0], BINCODE_FORMAT_VERSION);
+        assert_eq!(BincodeStorage::new(&path)?.get_embedding(&alice.id)?, Some(alice));
+        Ok(())
+    }
+}
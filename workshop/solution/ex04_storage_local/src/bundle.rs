@@ -0,0 +1,113 @@
+use super::{EmbeddingRecord, EmbeddingStorage};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+// Version of the JSON bundle layout written by `export_gallery`.
+pub const BUNDLE_VERSION: u32 = 1;
+
+// Backend-independent gallery backup: every record, plus a version for future layouts
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    version: u32,
+    records: Vec<EmbeddingRecord>,
+}
+
+// Write every record of `storage` to one JSON file at `path`, returning how many were written.
+pub fn export_gallery(storage: &dyn EmbeddingStorage, path: impl AsRef<Path>) -> Result<usize> {
+    let path = path.as_ref();
+    let records = storage.iter_embeddings().collect::<Result<Vec<_>>>()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(File::create(path).with_context(|| format!("Failed to create {}", path.display()))?);
+    let bundle = Bundle { version: BUNDLE_VERSION, records };
+    serde_json::to_writer(&mut writer, &bundle)?;
+    writer.flush()?;
+    Ok(bundle.records.len())
+}
+
+// Load a bundle written by `export_gallery` into `storage`, returning how many records were
+// added. Records whose id already exists in `storage` are skipped and left untouched, so
+// importing the same bundle twice is harmless. The new records are stored as one batch.
+pub fn import_gallery(storage: &mut dyn EmbeddingStorage, path: impl AsRef<Path>) -> Result<usize> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let bundle: Bundle =
+        serde_json::from_reader(BufReader::new(file)).with_context(|| format!("Failed to parse {}", path.display()))?;
+    if bundle.version != BUNDLE_VERSION {
+        anyhow::bail!("{} is bundle version {}, expected {}", path.display(), bundle.version, BUNDLE_VERSION);
+    }
+
+    let mut fresh = Vec::with_capacity(bundle.records.len());
+    for record in bundle.records {
+        if !storage.contains(&record.id)? {
+            fresh.push(record);
+        }
+    }
+    let imported = fresh.len();
+    storage.store_embeddings(fresh)?;
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{TempFileGuard, record};
+    use crate::{InMemoryStorage, SqliteStorage};
+    use uuid::Uuid;
+
+    fn sorted(mut records: Vec<EmbeddingRecord>) -> Vec<EmbeddingRecord> {
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        records
+    }
+
+    #[test]
+    fn gallery_round_trips_through_a_bundle() -> Result<()> {
+        let path = format!("workshop_bundle_{}.json", Uuid::new_v4());
+        let db_path = format!("workshop_bundle_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let _db_guard = TempFileGuard { path: db_path.clone() };
+
+        let mut source = InMemoryStorage::new();
+        for i in 0..5 {
+            source.store_embedding(record(&format!("person_{i}"), vec![i as f32, 0.5, -1.0]))?;
+        }
+        assert_eq!(export_gallery(&source, &path)?, 5);
+
+        let mut memory = InMemoryStorage::new();
+        assert_eq!(import_gallery(&mut memory, &path)?, 5);
+        assert_eq!(sorted(memory.get_all_embeddings()?), sorted(source.get_all_embeddings()?));
+
+        // Any backend can be the target
+        let mut sqlite = SqliteStorage::new(&db_path)?;
+        assert_eq!(import_gallery(&mut sqlite, &path)?, 5);
+        assert_eq!(sorted(sqlite.get_all_embeddings()?), sorted(source.get_all_embeddings()?));
+        Ok(())
+    }
+
+    #[test]
+    fn import_skips_ids_that_already_exist() -> Result<()> {
+        let path = format!("workshop_bundle_{}.json", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+
+        let alice = record("alice", vec![1.0, 0.0]);
+        let bob = record("bob", vec![0.0, 1.0]);
+        let mut source = InMemoryStorage::new();
+        source.store_embeddings(vec![alice.clone(), bob.clone()])?;
+        export_gallery(&source, &path)?;
+
+        let mut target = InMemoryStorage::new();
+        let mut local_alice = alice.clone();
+        local_alice.name = "alice (local)".to_string();
+        target.store_embedding(local_alice.clone())?;
+
+        assert_eq!(import_gallery(&mut target, &path)?, 1);
+        assert_eq!(target.get_embedding(&alice.id)?, Some(local_alice), "Existing record is kept");
+        assert_eq!(target.get_embedding(&bob.id)?, Some(bob));
+        assert_eq!(import_gallery(&mut target, &path)?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+// Helpers shared by the storage backends' tests and, through `TempFileGuard`, by the tests of the
+// crates built on this one
+#[cfg(test)]
+use super::EmbeddingRecord;
+#[cfg(test)]
+use std::collections::HashMap;
+#[cfg(test)]
+use uuid::Uuid;
+
+// A fresh record for `name`, with a random id and a `source` metadata entry so round trips
+// exercise metadata too
+#[cfg(test)]
+pub(crate) fn record(name: &str, embedding: Vec<f32>) -> EmbeddingRecord {
+    let mut metadata = HashMap::new();
+    metadata.insert("source".to_string(), format!("{name}.png"));
+    EmbeddingRecord {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        embedding,
+        created_at: chrono::Utc::now(),
+        metadata,
+        expires_at: None,
+    }
+}
+
+// Deletes the file at `path` when dropped, so a test cleans up its temp storage even if it fails
+pub struct TempFileGuard {
+    pub path: String,
+}
+
+impl Drop for TempFileGuard {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
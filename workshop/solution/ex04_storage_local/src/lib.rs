@@ -1,6 +1,6 @@
 use anyhow::Result;
+use face_auth_error::FaceAuthError;
 use serde::{Deserialize, Serialize};
-use serde_json;
 use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
 use std::io::{BufReader, BufWriter};
@@ -8,22 +8,290 @@ use std::path::Path;
 use std::sync::Mutex;
 use uuid::Uuid;
 
+mod async_storage;
+mod bincode_storage;
+mod bundle;
+mod fixtures;
+mod memory_storage;
+mod observed_storage;
+pub mod pca;
+#[cfg(feature = "pgvector")]
+mod pgvector_storage;
+pub mod quantize;
+#[cfg(feature = "redis")]
+mod redis_storage;
+mod shared_storage;
+mod sqlite_storage;
+pub use async_storage::{AsyncEmbeddingStorage, BlockingAdapter};
+pub use bincode_storage::{BINCODE_FORMAT_VERSION, BincodeStorage};
+pub use bundle::{BUNDLE_VERSION, export_gallery, import_gallery};
+#[doc(hidden)]
+pub use fixtures::TempFileGuard;
+pub use memory_storage::InMemoryStorage;
+pub use observed_storage::{IndexObserver, ObservedStorage};
+#[cfg(feature = "pgvector")]
+pub use pgvector_storage::PgVectorStorage;
+#[cfg(feature = "redis")]
+pub use redis_storage::RedisStorage;
+pub use shared_storage::SharedStorage;
+pub use sqlite_storage::SqliteStorage;
+
+/// Schema version written into every serialized record. Version 2 added `expires_at`.
+/// Records without a version predate versioning and load as version 1, with any missing
+/// fields defaulted; records from a newer version are refused rather than half-read.
+pub const RECORD_SCHEMA_VERSION: u32 = 2;
+
 // Define the EmbeddingRecord struct locally (not imported)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "SerializedRecord")]
 pub struct EmbeddingRecord {
     pub id: String,
     pub name: String,
     pub embedding: Vec<f32>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub metadata: HashMap<String, String>,
+    /// After this instant the record is ignored by searches and removed by `purge_expired`.
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+// On-disk form of a record. Fields added after version 1 must be `#[serde(default)]`, and
+// `SerializedRecordRef` must list the same fields in the same order (bincode is positional).
+#[derive(Deserialize)]
+struct SerializedRecord {
+    #[serde(default = "unversioned")]
+    version: u32,
+    id: String,
+    name: String,
+    embedding: Vec<f32>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+    #[serde(default)]
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize)]
+struct SerializedRecordRef<'a> {
+    version: u32,
+    id: &'a str,
+    name: &'a str,
+    embedding: &'a [f32],
+    created_at: &'a chrono::DateTime<chrono::Utc>,
+    metadata: &'a HashMap<String, String>,
+    expires_at: &'a Option<chrono::DateTime<chrono::Utc>>,
+}
+
+fn unversioned() -> u32 {
+    1
+}
+
+impl TryFrom<SerializedRecord> for EmbeddingRecord {
+    type Error = String;
+
+    fn try_from(record: SerializedRecord) -> Result<Self, Self::Error> {
+        if record.version > RECORD_SCHEMA_VERSION {
+            return Err(format!(
+                "Record {} has schema version {}, this build reads versions 1 to {}",
+                record.id, record.version, RECORD_SCHEMA_VERSION
+            ));
+        }
+        Ok(EmbeddingRecord {
+            id: record.id,
+            name: record.name,
+            embedding: record.embedding,
+            created_at: record.created_at,
+            metadata: record.metadata,
+            expires_at: record.expires_at,
+        })
+    }
+}
+
+impl Serialize for EmbeddingRecord {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedRecordRef {
+            version: RECORD_SCHEMA_VERSION,
+            id: &self.id,
+            name: &self.name,
+            embedding: &self.embedding,
+            created_at: &self.created_at,
+            metadata: &self.metadata,
+            expires_at: &self.expires_at,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Metadata key holding the collection a record belongs to.
+pub const COLLECTION_KEY: &str = "collection";
+/// Collection of records stored without one, e.g. through `store_embedding`.
+pub const DEFAULT_COLLECTION: &str = "default";
+/// Metadata key holding when a record was soft-deleted (RFC 3339). Such records stay in storage
+/// but are skipped by search until restored or purged.
+pub const DELETED_KEY: &str = "deleted_at";
+
+impl EmbeddingRecord {
+    /// The collection this record belongs to, `DEFAULT_COLLECTION` if none was set.
+    pub fn collection(&self) -> &str {
+        self.metadata.get(COLLECTION_KEY).map_or(DEFAULT_COLLECTION, String::as_str)
+    }
+
+    /// Whether the record has expired as of `now`; records without `expires_at` never do.
+    pub fn is_expired_at(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Whether the record has been soft-deleted, see `EmbeddingStorage::soft_delete`.
+    pub fn is_deleted(&self) -> bool {
+        self.metadata.contains_key(DELETED_KEY)
+    }
 }
 
 // Define the EmbeddingStorage trait locally (not imported)
 pub trait EmbeddingStorage {
     fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()>;
+    /// Store several records in one call. Backends with transactions apply the whole batch
+    /// atomically; the default simply stores them one by one.
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        for record in records {
+            self.store_embedding(record)?;
+        }
+        Ok(())
+    }
+    /// Look up a single record by id; `Ok(None)` if it does not exist.
     fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>>;
     fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>>;
+    /// Visit every record one at a time. Backends override this to avoid materializing the
+    /// whole gallery; the default falls back to `get_all_embeddings`.
+    fn iter_embeddings(&self) -> Box<dyn Iterator<Item = Result<EmbeddingRecord>> + '_> {
+        match self.get_all_embeddings() {
+            Ok(records) => Box::new(records.into_iter().map(Ok)),
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    }
+    /// One page of records in a stable order (`created_at`, then id), so consecutive pages neither
+    /// overlap nor skip records. An `offset` past the end yields an empty page.
+    /// The default sorts the whole gallery; backends with an index override it.
+    fn get_embeddings_page(&self, offset: usize, limit: usize) -> Result<Vec<EmbeddingRecord>> {
+        let mut records = self.get_all_embeddings()?;
+        records.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(records.into_iter().skip(offset).take(limit).collect())
+    }
     fn delete_embedding(&mut self, id: &str) -> Result<bool>;
+    /// Replace the record with the same id; errors if no such record exists.
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()>;
+    /// Number of stored records. The default counts a full scan; backends override it.
+    fn count(&self) -> Result<usize> {
+        self.iter_embeddings().try_fold(0, |count, record| record.map(|_| count + 1))
+    }
+    /// Whether a record with this id exists. The default fetches it; backends override it.
+    fn contains(&self, id: &str) -> Result<bool> {
+        Ok(self.get_embedding(id)?.is_some())
+    }
+    /// Length of the stored embeddings, `None` while the storage is empty. The first record
+    /// stored fixes it; records of any other length are rejected with `DimensionMismatch`.
+    fn dimension(&self) -> Result<Option<usize>> {
+        Ok(self.get_all_embeddings()?.first().map(|record| record.embedding.len()))
+    }
+    /// The `limit` records most similar to `query` by cosine similarity, most similar first,
+    /// skipping expired and soft-deleted records. Backends that can rank inside the database
+    /// override this; the default returns `None` so callers score the records themselves.
+    fn nearest(&self, _query: &[f32], _limit: usize) -> Result<Option<Vec<(EmbeddingRecord, f32)>>> {
+        Ok(None)
+    }
+
+    /// Delete every record whose `expires_at` has passed, returning how many were removed.
+    fn purge_expired(&mut self) -> Result<usize> {
+        let now = chrono::Utc::now();
+        purge_matching(self, |record| record.is_expired_at(now))
+    }
+
+    /// Mark the record inactive without removing it: it keeps its embedding and metadata (and so
+    /// its audit trail) but search skips it. Returns false if there is no such record or it is
+    /// already soft-deleted.
+    fn soft_delete(&mut self, id: &str) -> Result<bool> {
+        let Some(mut record) = self.get_embedding(id)? else {
+            return Ok(false);
+        };
+        if record.is_deleted() {
+            return Ok(false);
+        }
+        record.metadata.insert(DELETED_KEY.to_string(), chrono::Utc::now().to_rfc3339());
+        self.update_embedding(record)?;
+        Ok(true)
+    }
+
+    /// Undo `soft_delete`. Returns false if there is no such record or it is not soft-deleted.
+    fn restore(&mut self, id: &str) -> Result<bool> {
+        let Some(mut record) = self.get_embedding(id)? else {
+            return Ok(false);
+        };
+        if record.metadata.remove(DELETED_KEY).is_none() {
+            return Ok(false);
+        }
+        self.update_embedding(record)?;
+        Ok(true)
+    }
+
+    /// Permanently delete every soft-deleted record, returning how many were removed.
+    fn purge_deleted(&mut self) -> Result<usize> {
+        purge_matching(self, EmbeddingRecord::is_deleted)
+    }
+
+    // Collections are a tag in the record metadata, so one file or database can hold several
+    // galleries. The unscoped methods above still see every collection.
+
+    /// Store `record` in `collection`, overwriting any collection tag it already carries.
+    fn store_embedding_in(&mut self, collection: &str, mut record: EmbeddingRecord) -> Result<()> {
+        record.metadata.insert(COLLECTION_KEY.to_string(), collection.to_string());
+        self.store_embedding(record)
+    }
+    /// All records of one collection.
+    fn get_all_embeddings_in(&self, collection: &str) -> Result<Vec<EmbeddingRecord>> {
+        self.iter_embeddings_in(collection).collect()
+    }
+    /// Stream the records of one collection.
+    fn iter_embeddings_in<'a>(&'a self, collection: &'a str) -> Box<dyn Iterator<Item = Result<EmbeddingRecord>> + 'a> {
+        Box::new(self.iter_embeddings().filter(move |record| match record {
+            Ok(record) => record.collection() == collection,
+            Err(_) => true,
+        }))
+    }
+}
+
+// Shared by the purge methods: collect matching ids first, then delete them one by one
+fn purge_matching<S: EmbeddingStorage + ?Sized>(storage: &mut S, matches: impl Fn(&EmbeddingRecord) -> bool) -> Result<usize> {
+    let mut doomed = Vec::new();
+    for record in storage.iter_embeddings() {
+        let record = record?;
+        if matches(&record) {
+            doomed.push(record.id);
+        }
+    }
+    let mut removed = 0;
+    for id in doomed {
+        if storage.delete_embedding(&id)? {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Reject records whose length differs from `dimension` (or, for an empty storage, from each other).
+pub(crate) fn check_dimensions<'a>(
+    dimension: Option<usize>,
+    records: impl IntoIterator<Item = &'a EmbeddingRecord>,
+) -> Result<(), FaceAuthError> {
+    let mut expected = dimension;
+    for record in records {
+        let actual = record.embedding.len();
+        match expected {
+            Some(expected) if expected != actual => {
+                return Err(FaceAuthError::DimensionMismatch { expected, actual });
+            }
+            _ => expected = Some(actual),
+        }
+    }
+    Ok(())
 }
 
 // Simple local file storage implementation
@@ -102,6 +370,7 @@ impl LocalFileStorage {
 
 impl EmbeddingStorage for LocalFileStorage {
     fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        check_dimensions(self.dimension()?, [&record])?;
         if let Ok(mut guard) = self.data.lock() {
             guard.insert(record.id.clone(), record);
         }
@@ -109,6 +378,18 @@ impl EmbeddingStorage for LocalFileStorage {
         Ok(())
     }
 
+    // Insert the whole batch, then write the file once
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        check_dimensions(self.dimension()?, &records)?;
+        if let Ok(mut guard) = self.data.lock() {
+            for record in records {
+                guard.insert(record.id.clone(), record);
+            }
+        }
+        self.save_data()?;
+        Ok(())
+    }
+
     fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
         if let Ok(guard) = self.data.lock() {
             Ok(guard.get(id).cloned())
@@ -125,6 +406,18 @@ impl EmbeddingStorage for LocalFileStorage {
         }
     }
 
+    // Only the ids are snapshotted up front; each record is cloned out when it is reached
+    fn iter_embeddings(&self) -> Box<dyn Iterator<Item = Result<EmbeddingRecord>> + '_> {
+        let ids: Vec<String> = match self.data.lock() {
+            Ok(guard) => guard.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        Box::new(
+            ids.into_iter()
+                .filter_map(move |id| self.data.lock().ok()?.get(&id).cloned().map(Ok)),
+        )
+    }
+
     fn delete_embedding(&mut self, id: &str) -> Result<bool> {
         let deleted = if let Ok(mut guard) = self.data.lock() {
             guard.remove(id).is_some()
@@ -138,6 +431,34 @@ impl EmbeddingStorage for LocalFileStorage {
         
         Ok(deleted)
     }
+
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        check_dimensions(self.dimension()?, [&record])?;
+        if let Ok(mut guard) = self.data.lock() {
+            match guard.get_mut(&record.id) {
+                Some(existing) => *existing = record,
+                None => return Err(FaceAuthError::NotFound(record.id).into()),
+            }
+        }
+        self.save_data()?;
+        Ok(())
+    }
+
+    fn count(&self) -> Result<usize> {
+        Ok(self.data.lock().map(|guard| guard.len()).unwrap_or(0))
+    }
+
+    fn contains(&self, id: &str) -> Result<bool> {
+        Ok(self.data.lock().map(|guard| guard.contains_key(id)).unwrap_or(false))
+    }
+
+    fn dimension(&self) -> Result<Option<usize>> {
+        if let Ok(guard) = self.data.lock() {
+            Ok(guard.values().next().map(|record| record.embedding.len()))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 pub fn open_temp_storage() -> Result<(Box<dyn EmbeddingStorage>, String)> {
@@ -0,0 +1,237 @@
+use super::{EmbeddingRecord, EmbeddingStorage, check_dimensions};
+use anyhow::{Context, Result};
+use face_auth_error::FaceAuthError;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+// Purely in-memory storage; durability is opt-in through JSON snapshots
+// written in the same format as `LocalFileStorage`
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryStorage {
+    data: HashMap<String, EmbeddingRecord>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Write all records to `path`. The snapshot goes to a temporary file first and is then
+    // renamed over the target, so a crash mid-write leaves the previous snapshot intact.
+    pub fn save_snapshot(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = Path::new(&tmp_path);
+        {
+            let mut writer = BufWriter::new(File::create(tmp_path)?);
+            serde_json::to_writer_pretty(&mut writer, &self.data)?;
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        fs::rename(tmp_path, path)
+            .with_context(|| format!("Failed to move snapshot into place at {}", path.display()))?;
+        Ok(())
+    }
+
+    // Replace the current contents with the records from a snapshot
+    pub fn load_snapshot(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open snapshot {}", path.display()))?;
+        self.data = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Failed to parse snapshot {}", path.display()))?;
+        Ok(())
+    }
+}
+
+impl EmbeddingStorage for InMemoryStorage {
+    fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        check_dimensions(self.dimension()?, [&record])?;
+        self.data.insert(record.id.clone(), record);
+        Ok(())
+    }
+
+    fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+        Ok(self.data.get(id).cloned())
+    }
+
+    fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+        Ok(self.data.values().cloned().collect())
+    }
+
+    fn iter_embeddings(&self) -> Box<dyn Iterator<Item = Result<EmbeddingRecord>> + '_> {
+        Box::new(self.data.values().cloned().map(Ok))
+    }
+
+    fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+        Ok(self.data.remove(id).is_some())
+    }
+
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        check_dimensions(self.dimension()?, [&record])?;
+        match self.data.get_mut(&record.id) {
+            Some(existing) => *existing = record,
+            None => return Err(FaceAuthError::NotFound(record.id).into()),
+        }
+        Ok(())
+    }
+
+    // Checked up front so a rejected batch stores nothing
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        check_dimensions(self.dimension()?, &records)?;
+        for record in records {
+            self.data.insert(record.id.clone(), record);
+        }
+        Ok(())
+    }
+
+    fn count(&self) -> Result<usize> {
+        Ok(self.data.len())
+    }
+
+    fn contains(&self, id: &str) -> Result<bool> {
+        Ok(self.data.contains_key(id))
+    }
+
+    fn dimension(&self) -> Result<Option<usize>> {
+        Ok(self.data.values().next().map(|record| record.embedding.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{TempFileGuard, record};
+    use crate::LocalFileStorage;
+    use uuid::Uuid;
+
+    fn sorted(mut records: Vec<EmbeddingRecord>) -> Vec<EmbeddingRecord> {
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        records
+    }
+
+    #[test]
+    fn snapshot_round_trip_restores_records() -> Result<()> {
+        let path = format!("workshop_snapshot_{}.json", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+
+        let mut storage = InMemoryStorage::new();
+        storage.store_embedding(record("alice", vec![0.1, 0.2, 0.3]))?;
+        storage.store_embedding(record("bob", vec![-1.0, 0.5, 2.25]))?;
+        storage.save_snapshot(&path)?;
+        assert!(!Path::new(&format!("{path}.tmp")).exists(), "Temporary snapshot should be renamed away");
+
+        let mut restored = InMemoryStorage::new();
+        restored.store_embedding(record("stale", vec![9.0]))?;
+        restored.load_snapshot(&path)?;
+        assert_eq!(sorted(restored.get_all_embeddings()?), sorted(storage.get_all_embeddings()?));
+
+        // Snapshots share the on-disk format of LocalFileStorage
+        let local = LocalFileStorage::new(path.clone())?;
+        assert_eq!(sorted(local.get_all_embeddings()?), sorted(storage.get_all_embeddings()?));
+        Ok(())
+    }
+
+    #[test]
+    fn loading_missing_snapshot_keeps_contents() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let alice = record("alice", vec![1.0, 0.0]);
+        storage.store_embedding(alice.clone())?;
+
+        assert!(storage.load_snapshot(format!("missing_{}.json", Uuid::new_v4())).is_err());
+        assert_eq!(storage.get_embedding(&alice.id)?, Some(alice));
+        let err = storage.update_embedding(record("nobody", vec![1.0, 0.0])).unwrap_err();
+        assert!(matches!(err.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::NotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn first_record_locks_the_dimension() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        assert_eq!(storage.dimension()?, None);
+        storage.store_embedding(record("alice", vec![0.1; 512]))?;
+        assert_eq!(storage.dimension()?, Some(512));
+
+        let err = storage.store_embedding(record("bob", vec![0.1; 128])).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FaceAuthError>(),
+            Some(FaceAuthError::DimensionMismatch { expected: 512, actual: 128 })
+        ));
+        let batch = vec![record("carol", vec![0.2; 512]), record("dave", vec![0.2; 3])];
+        assert!(storage.store_embeddings(batch).is_err());
+        assert_eq!(storage.get_all_embeddings()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn iter_embeddings_matches_get_all() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        for i in 0..10 {
+            storage.store_embedding(record(&format!("person_{i}"), vec![i as f32, 1.0]))?;
+        }
+        let streamed = storage.iter_embeddings().collect::<Result<Vec<_>>>()?;
+        assert_eq!(sorted(streamed), sorted(storage.get_all_embeddings()?));
+        Ok(())
+    }
+
+    #[test]
+    fn purge_expired_removes_only_past_records() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let now = chrono::Utc::now();
+        let mut expired = record("expired", vec![1.0, 0.0]);
+        expired.expires_at = Some(now - chrono::Duration::minutes(5));
+        let mut pending = record("pending", vec![1.0, 0.0]);
+        pending.expires_at = Some(now + chrono::Duration::days(1));
+        let permanent = record("permanent", vec![1.0, 0.0]);
+        storage.store_embeddings(vec![expired.clone(), pending.clone(), permanent.clone()])?;
+
+        assert!(expired.is_expired_at(now));
+        assert!(!pending.is_expired_at(now) && !permanent.is_expired_at(now));
+        assert_eq!(storage.purge_expired()?, 1);
+        assert_eq!(storage.get_embedding(&expired.id)?, None);
+        assert_eq!(sorted(storage.get_all_embeddings()?), sorted(vec![pending, permanent]));
+        assert_eq!(storage.purge_expired()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn version_one_records_upgrade_with_defaults() -> Result<()> {
+        let path = format!("workshop_snapshot_{}.json", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let v1 = r#"{
+            "legacy": {
+                "id": "legacy",
+                "name": "alice",
+                "embedding": [0.25, -0.5],
+                "created_at": "2024-03-01T12:00:00Z"
+            }
+        }"#;
+        fs::write(&path, v1)?;
+
+        let mut storage = InMemoryStorage::new();
+        storage.load_snapshot(&path)?;
+        let record = storage.get_embedding("legacy")?.expect("v1 record should load");
+        assert_eq!((record.name.as_str(), record.embedding.as_slice()), ("alice", &[0.25, -0.5][..]));
+        assert!(record.metadata.is_empty());
+        assert_eq!(record.expires_at, None);
+
+        // Saving writes the current schema version
+        storage.save_snapshot(&path)?;
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        assert_eq!(saved["legacy"]["version"], crate::RECORD_SCHEMA_VERSION);
+        Ok(())
+    }
+
+    #[test]
+    fn future_schema_version_is_rejected() {
+        let json = r#"{"version": 99, "id": "x", "name": "x", "embedding": [1.0], "created_at": "2024-03-01T12:00:00Z"}"#;
+        let err = serde_json::from_str::<EmbeddingRecord>(json).unwrap_err();
+        assert!(err.to_string().contains("schema version 99"), "Unexpected error: {err}");
+    }
+}
@@ -0,0 +1,183 @@
+use super::{EmbeddingRecord, EmbeddingStorage};
+use anyhow::Result;
+use face_auth_error::FaceAuthError;
+use std::sync::{Arc, Mutex};
+
+// Receives every write that goes through an `ObservedStorage`, so a derived structure such as an
+// ANN index can follow the gallery without being rebuilt. An error from an observer is returned
+// from the write even though the storage itself has already been changed.
+pub trait IndexObserver {
+    // Called after `record` is stored. Storing over an existing id is reported here too,
+    // as is the new version of a record passed to `update_embedding`.
+    fn on_insert(&mut self, record: &EmbeddingRecord) -> Result<()>;
+    // Called after a record has actually been removed
+    fn on_delete(&mut self, id: &str) -> Result<()>;
+}
+
+// Lets the caller keep a handle on an observer (e.g. to query the index) after attaching a clone
+impl<T: IndexObserver> IndexObserver for Arc<Mutex<T>> {
+    fn on_insert(&mut self, record: &EmbeddingRecord) -> Result<()> {
+        self.lock().map_err(|_| poisoned())?.on_insert(record)
+    }
+
+    fn on_delete(&mut self, id: &str) -> Result<()> {
+        self.lock().map_err(|_| poisoned())?.on_delete(id)
+    }
+}
+
+fn poisoned() -> FaceAuthError {
+    FaceAuthError::StorageError("Index observer lock poisoned by a panicked writer".into())
+}
+
+// Wraps a storage and notifies its observers, in registration order, after each successful write.
+// Writes that fail in the inner storage notify no one.
+pub struct ObservedStorage<S: EmbeddingStorage> {
+    inner: S,
+    observers: Vec<Box<dyn IndexObserver + Send>>,
+}
+
+impl<S: EmbeddingStorage> ObservedStorage<S> {
+    pub fn new(storage: S) -> Self {
+        ObservedStorage {
+            inner: storage,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn IndexObserver + Send>) {
+        self.observers.push(observer);
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn notify_insert(&mut self, record: &EmbeddingRecord) -> Result<()> {
+        for observer in &mut self.observers {
+            observer.on_insert(record)?;
+        }
+        Ok(())
+    }
+
+    fn notify_delete(&mut self, id: &str) -> Result<()> {
+        for observer in &mut self.observers {
+            observer.on_delete(id)?;
+        }
+        Ok(())
+    }
+}
+
+// `purge_expired` and the collection helpers keep their default implementations, which write
+// through the methods below and so notify the observers too.
+impl<S: EmbeddingStorage> EmbeddingStorage for ObservedStorage<S> {
+    fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        self.inner.store_embedding(record.clone())?;
+        self.notify_insert(&record)
+    }
+
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        self.inner.store_embeddings(records.clone())?;
+        for record in &records {
+            self.notify_insert(record)?;
+        }
+        Ok(())
+    }
+
+    fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+        self.inner.get_embedding(id)
+    }
+
+    fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+        self.inner.get_all_embeddings()
+    }
+
+    fn iter_embeddings(&self) -> Box<dyn Iterator<Item = Result<EmbeddingRecord>> + '_> {
+        self.inner.iter_embeddings()
+    }
+
+    fn get_embeddings_page(&self, offset: usize, limit: usize) -> Result<Vec<EmbeddingRecord>> {
+        self.inner.get_embeddings_page(offset, limit)
+    }
+
+    fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+        let removed = self.inner.delete_embedding(id)?;
+        if removed {
+            self.notify_delete(id)?;
+        }
+        Ok(removed)
+    }
+
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        self.inner.update_embedding(record.clone())?;
+        self.notify_insert(&record)
+    }
+
+    fn count(&self) -> Result<usize> {
+        self.inner.count()
+    }
+
+    fn contains(&self, id: &str) -> Result<bool> {
+        self.inner.contains(id)
+    }
+
+    fn dimension(&self) -> Result<Option<usize>> {
+        self.inner.dimension()
+    }
+
+    fn nearest(&self, query: &[f32], limit: usize) -> Result<Option<Vec<(EmbeddingRecord, f32)>>> {
+        self.inner.nearest(query, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InMemoryStorage, fixtures};
+
+    // A fixture record whose id is `id`, so updates can target it
+    fn record(id: &str, embedding: Vec<f32>) -> EmbeddingRecord {
+        EmbeddingRecord {
+            id: id.to_string(),
+            ..fixtures::record(id, embedding)
+        }
+    }
+
+    struct Recorder {
+        label: &'static str,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl IndexObserver for Recorder {
+        fn on_insert(&mut self, record: &EmbeddingRecord) -> Result<()> {
+            self.events.lock().unwrap().push(format!("{} +{}", self.label, record.id));
+            Ok(())
+        }
+
+        fn on_delete(&mut self, id: &str) -> Result<()> {
+            self.events.lock().unwrap().push(format!("{} -{}", self.label, id));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn observers_see_writes_in_registration_order() -> Result<()> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut storage = ObservedStorage::new(InMemoryStorage::new());
+        for label in ["first", "second"] {
+            storage.add_observer(Box::new(Recorder { label, events: Arc::clone(&events) }));
+        }
+
+        storage.store_embedding(record("a", vec![1.0, 0.0]))?;
+        storage.update_embedding(record("a", vec![0.0, 1.0]))?;
+        assert!(storage.delete_embedding("a")?);
+        assert!(!storage.delete_embedding("a")?, "Deleting a missing id notifies no one");
+        assert!(storage.store_embedding(record("b", vec![1.0, 0.0, 0.0])).is_ok());
+        assert!(storage.update_embedding(record("missing", vec![1.0, 0.0, 0.0])).is_err());
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["first +a", "second +a", "first +a", "second +a", "first -a", "second -a", "first +b", "second +b"]
+        );
+        Ok(())
+    }
+}
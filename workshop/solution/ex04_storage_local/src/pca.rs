@@ -0,0 +1,181 @@
+// Principal component analysis for shrinking embeddings: `fit` learns the mean and the top
+// principal axes of a sample, `transform` centers a new embedding and projects it onto them.
+// The fitted model is serde-serializable so the same projection can be applied at query time.
+
+use anyhow::Result;
+use face_auth_error::FaceAuthError;
+use serde::{Deserialize, Serialize};
+
+// Power iterations per component; convergence usually happens far sooner
+const MAX_ITERATIONS: usize = 500;
+const TOLERANCE: f32 = 1e-7;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pca {
+    mean: Vec<f32>,
+    // Unit-length principal axes, most variance first
+    components: Vec<Vec<f32>>,
+}
+
+impl Pca {
+    pub fn fit(embeddings: &[Vec<f32>], target_dim: usize) -> Result<Self> {
+        let Some(first) = embeddings.first() else {
+            anyhow::bail!("Cannot fit PCA on an empty set of embeddings");
+        };
+        let dims = first.len();
+        if target_dim == 0 || target_dim > dims {
+            anyhow::bail!("Target dimension must be between 1 and {}, got {}", dims, target_dim);
+        }
+        for embedding in embeddings {
+            if embedding.len() != dims {
+                return Err(FaceAuthError::DimensionMismatch { expected: dims, actual: embedding.len() }.into());
+            }
+        }
+
+        let count = embeddings.len() as f32;
+        let mut mean = vec![0f32; dims];
+        for embedding in embeddings {
+            for (m, v) in mean.iter_mut().zip(embedding) {
+                *m += v / count;
+            }
+        }
+
+        // Covariance matrix, row-major dims x dims
+        let mut covariance = vec![0f32; dims * dims];
+        for embedding in embeddings {
+            let centered: Vec<f32> = embedding.iter().zip(&mean).map(|(v, m)| v - m).collect();
+            for (i, &ci) in centered.iter().enumerate() {
+                let row = &mut covariance[i * dims..(i + 1) * dims];
+                for (cell, &cj) in row.iter_mut().zip(&centered) {
+                    *cell += ci * cj / count;
+                }
+            }
+        }
+
+        let mut components: Vec<Vec<f32>> = Vec::with_capacity(target_dim);
+        for k in 0..target_dim {
+            let component = power_iteration(&covariance, dims, &components, k);
+            components.push(component);
+        }
+        Ok(Pca { mean, components })
+    }
+
+    pub fn input_dim(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn output_dim(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn transform(&self, embedding: &[f32]) -> Result<Vec<f32>, FaceAuthError> {
+        if embedding.len() != self.input_dim() {
+            return Err(FaceAuthError::DimensionMismatch { expected: self.input_dim(), actual: embedding.len() });
+        }
+        let centered: Vec<f32> = embedding.iter().zip(&self.mean).map(|(v, m)| v - m).collect();
+        Ok(self.components.iter().map(|axis| dot(axis, &centered)).collect())
+    }
+}
+
+// Leading eigenvector of `covariance` orthogonal to the `found` ones. The start vector is a
+// fixed function of `k`, so fitting the same data always gives the same axes.
+fn power_iteration(covariance: &[f32], dims: usize, found: &[Vec<f32>], k: usize) -> Vec<f32> {
+    let mut vector: Vec<f32> = (0..dims).map(|i| (((i + 1) * (k + 3) * 7919) % 1009) as f32 / 1009.0 - 0.5).collect();
+    orthonormalize(&mut vector, found);
+    for _ in 0..MAX_ITERATIONS {
+        let mut next: Vec<f32> = covariance.chunks_exact(dims).map(|row| dot(row, &vector)).collect();
+        orthonormalize(&mut next, found);
+        let change: f32 = next.iter().zip(&vector).map(|(a, b)| (a - b).powi(2)).sum();
+        vector = next;
+        if change < TOLERANCE {
+            break;
+        }
+    }
+    vector
+}
+
+// Gram-Schmidt against the previous axes, then scale to unit length
+fn orthonormalize(vector: &mut [f32], found: &[Vec<f32>]) {
+    for axis in found {
+        let projection = dot(vector, axis);
+        for (v, a) in vector.iter_mut().zip(axis) {
+            *v -= projection * a;
+        }
+    }
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot(a, b) / (norm(a) * norm(b))
+    }
+
+    // Indices of the `k` gallery entries most similar to `query`
+    fn top_indices(gallery: &[Vec<f32>], query: &[f32], k: usize) -> Vec<usize> {
+        let mut ranked: Vec<(usize, f32)> = gallery.iter().map(|g| cosine(g, query)).enumerate().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.into_iter().take(k).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn projection_preserves_cosine_rankings() -> Result<()> {
+        // 128-d embeddings that really live near a 12-d subspace, plus a little noise
+        let mut rng = StdRng::seed_from_u64(3);
+        let (dims, latent_dims) = (128, 12);
+        let mixing: Vec<Vec<f32>> = (0..latent_dims).map(|_| (0..dims).map(|_| rng.random_range(-1.0..1.0)).collect()).collect();
+        let mut sample = || -> Vec<f32> {
+            let latent: Vec<f32> = (0..latent_dims).map(|_| rng.random_range(-1.0..1.0)).collect();
+            (0..dims)
+                .map(|d| latent.iter().zip(&mixing).map(|(l, row)| l * row[d]).sum::<f32>() + rng.random_range(-0.05..0.05))
+                .collect()
+        };
+        let gallery: Vec<Vec<f32>> = (0..300).map(|_| sample()).collect();
+        let queries: Vec<Vec<f32>> = (0..20).map(|_| sample()).collect();
+
+        let pca = Pca::fit(&gallery, latent_dims)?;
+        assert_eq!((pca.input_dim(), pca.output_dim()), (dims, latent_dims));
+        let projected = gallery.iter().map(|g| pca.transform(g)).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(projected[0].len(), latent_dims);
+
+        let (mut kept, mut total) = (0, 0);
+        for query in &queries {
+            let exact = top_indices(&gallery, query, 10);
+            let approx = top_indices(&projected, &pca.transform(query)?, 10);
+            kept += approx.iter().filter(|i| exact.contains(i)).count();
+            total += exact.len();
+        }
+        let overlap = kept as f32 / total as f32;
+        assert!(overlap > 0.8, "Only {} of the top-10 neighbours survived projection", overlap);
+        Ok(())
+    }
+
+    #[test]
+    fn fitted_model_round_trips_through_serde() -> Result<()> {
+        let data = vec![vec![2.0, 0.0, 1.0], vec![-2.0, 0.1, 1.0], vec![1.0, -0.1, 1.0], vec![-1.0, 0.0, 1.0]];
+        let pca = Pca::fit(&data, 1)?;
+        // Everything varies along the first axis, so that is the principal component
+        assert!(pca.components[0][0].abs() > 0.99);
+
+        let restored: Pca = serde_json::from_str(&serde_json::to_string(&pca)?)?;
+        assert_eq!(restored, pca);
+        assert_eq!(restored.transform(&data[0])?, pca.transform(&data[0])?);
+
+        assert!(matches!(pca.transform(&[1.0, 2.0]), Err(FaceAuthError::DimensionMismatch { expected: 3, actual: 2 })));
+        assert!(Pca::fit(&data, 4).is_err());
+        assert!(Pca::fit(&[], 1).is_err());
+        Ok(())
+    }
+}
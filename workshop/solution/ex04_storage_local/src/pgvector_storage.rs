@@ -0,0 +1,313 @@
+use super::{EmbeddingRecord, EmbeddingStorage, check_dimensions};
+use anyhow::{Context, Result};
+use chrono::{DateTime, SubsecRound, Utc};
+use face_auth_error::FaceAuthError;
+use pgvector::Vector;
+use postgres::{GenericClient, NoTls, Row};
+use r2d2::{Pool, PooledConnection};
+use r2d2_postgres::PostgresConnectionManager;
+
+type Manager = PostgresConnectionManager<NoTls>;
+
+const DEFAULT_POOL_SIZE: u32 = 8;
+// Bounds of pgvector's `hnsw.ef_search`; the lower one is its default
+const MIN_EF_SEARCH: usize = 40;
+const MAX_EF_SEARCH: usize = 1000;
+const SELECT_COLUMNS: &str = "SELECT id, name, embedding, created_at, metadata, expires_at FROM embeddings";
+
+// Postgres storage using the pgvector extension. Embeddings live in a `vector(dims)` column
+// with an HNSW cosine index, so nearest-neighbour search runs inside the database instead of
+// scoring every record in Rust. Connections come from a pool shared by all clones of the pool.
+// Timestamps are stored to the microsecond, the resolution of `TIMESTAMPTZ`.
+pub struct PgVectorStorage {
+    pool: Pool<Manager>,
+    dims: usize,
+}
+
+impl PgVectorStorage {
+    // `url` is a libpq connection string such as `host=localhost user=postgres`.
+    // Creates the extension, table and index if missing; the column is fixed to `dims` values,
+    // and an existing table whose column has another length is rejected with `DimensionMismatch`.
+    pub fn new(url: &str, dims: usize) -> Result<Self> {
+        Self::with_pool_size(url, dims, DEFAULT_POOL_SIZE)
+    }
+
+    pub fn with_pool_size(url: &str, dims: usize, pool_size: u32) -> Result<Self> {
+        if dims == 0 {
+            anyhow::bail!("pgvector columns need at least one dimension");
+        }
+        let manager = Manager::new(url.parse().context("Invalid Postgres connection string")?, NoTls);
+        let pool = Pool::builder()
+            .max_size(pool_size)
+            .build(manager)
+            .map_err(|e| FaceAuthError::StorageError(Box::new(e)))
+            .context("Failed to connect to Postgres")?;
+        let storage = PgVectorStorage { pool, dims };
+        storage.connection()?.batch_execute(&format!(
+            "CREATE EXTENSION IF NOT EXISTS vector;
+             CREATE TABLE IF NOT EXISTS embeddings (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                embedding vector({dims}) NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL,
+                metadata TEXT NOT NULL,
+                expires_at TIMESTAMPTZ
+             );
+             CREATE INDEX IF NOT EXISTS embeddings_embedding_idx
+                ON embeddings USING hnsw (embedding vector_cosine_ops);"
+        ))?;
+        storage.check_column()?;
+        Ok(storage)
+    }
+
+    // `CREATE TABLE IF NOT EXISTS` leaves an existing table alone, so make sure its column
+    // holds `dims` values. pgvector keeps the dimension as the column's type modifier.
+    fn check_column(&self) -> Result<()> {
+        let existing: i32 = self
+            .connection()?
+            .query_one(
+                "SELECT atttypmod FROM pg_attribute
+                 WHERE attrelid = 'embeddings'::regclass AND attname = 'embedding'",
+                &[],
+            )?
+            .get(0);
+        if i32::try_from(self.dims).ok() != Some(existing) {
+            return Err(FaceAuthError::DimensionMismatch {
+                expected: usize::try_from(existing).unwrap_or(0),
+                actual: self.dims,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    // The `limit` records nearest to `query` by cosine similarity, most similar first,
+    // skipping expired and soft-deleted records. Ordered by distance alone so Postgres can
+    // walk the HNSW index; ties are then broken by `created_at` and id here, as in the
+    // retrieval crate. The index filters after its candidate search, so when many records
+    // are expired or soft-deleted fewer than `limit` may come back. The candidate list is
+    // widened to `limit` (up to pgvector's maximum of 1000) to keep that rare.
+    pub fn search_similar(&self, query: &[f32], limit: usize) -> Result<Vec<(EmbeddingRecord, f32)>> {
+        self.check_query(query)?;
+        let mut conn = self.connection()?;
+        let mut tx = conn.transaction()?;
+        tx.batch_execute(&format!(
+            "SET LOCAL hnsw.ef_search = {}",
+            limit.clamp(MIN_EF_SEARCH, MAX_EF_SEARCH)
+        ))?;
+        let rows = tx.query(
+            "SELECT id, name, embedding, created_at, metadata, expires_at, 1 - (embedding <=> $1) AS similarity
+             FROM embeddings
+             WHERE (expires_at IS NULL OR expires_at > now()) AND NOT (metadata::jsonb ? 'deleted_at')
+             ORDER BY embedding <=> $1
+             LIMIT $2",
+            &[&Vector::from(query.to_vec()), &to_i64(limit)],
+        )?;
+        tx.commit()?;
+        let mut results = rows
+            .iter()
+            .map(|row| Ok((decode_record(row)?, row.try_get::<_, f64>("similarity")? as f32)))
+            .collect::<Result<Vec<_>>>()?;
+        results.sort_by(|(a, sa), (b, sb)| {
+            sb.total_cmp(sa)
+                .then_with(|| a.created_at.cmp(&b.created_at))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(results)
+    }
+
+    // Same ranking as `search_similar`; named after the brute-force helper in the retrieval crate
+    pub fn top_k(&self, query: &[f32], k: usize) -> Result<Vec<(EmbeddingRecord, f32)>> {
+        self.search_similar(query, k)
+    }
+
+    fn connection(&self) -> Result<PooledConnection<Manager>> {
+        self.pool
+            .get()
+            .map_err(|e| FaceAuthError::StorageError(Box::new(e)))
+            .context("No Postgres connection available")
+    }
+
+    fn check_query(&self, query: &[f32]) -> Result<(), FaceAuthError> {
+        if query.len() != self.dims {
+            return Err(FaceAuthError::DimensionMismatch {
+                expected: self.dims,
+                actual: query.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn to_i64(n: usize) -> i64 {
+    i64::try_from(n).unwrap_or(i64::MAX)
+}
+
+fn decode_record(row: &Row) -> Result<EmbeddingRecord> {
+    let embedding: Vector = row.try_get("embedding")?;
+    let metadata: String = row.try_get("metadata")?;
+    Ok(EmbeddingRecord {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        embedding: embedding.to_vec(),
+        created_at: row.try_get("created_at")?,
+        metadata: serde_json::from_str(&metadata)?,
+        expires_at: row.try_get("expires_at")?,
+    })
+}
+
+fn upsert(client: &mut impl GenericClient, record: &EmbeddingRecord) -> Result<()> {
+    client.execute(
+        "INSERT INTO embeddings (id, name, embedding, created_at, metadata, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (id) DO UPDATE SET
+            name = excluded.name,
+            embedding = excluded.embedding,
+            created_at = excluded.created_at,
+            metadata = excluded.metadata,
+            expires_at = excluded.expires_at",
+        &[
+            &record.id,
+            &record.name,
+            &Vector::from(record.embedding.clone()),
+            &to_micros(record.created_at),
+            &serde_json::to_string(&record.metadata)?,
+            &record.expires_at.map(to_micros),
+        ],
+    )?;
+    Ok(())
+}
+
+// Drop what `TIMESTAMPTZ` cannot hold, explicitly rather than leaving it to the driver
+fn to_micros(at: DateTime<Utc>) -> DateTime<Utc> {
+    at.trunc_subsecs(6)
+}
+
+impl EmbeddingStorage for PgVectorStorage {
+    fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        check_dimensions(Some(self.dims), [&record])?;
+        upsert(&mut *self.connection()?, &record)
+    }
+
+    // One transaction, so a failure midway stores nothing
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        check_dimensions(Some(self.dims), &records)?;
+        let mut conn = self.connection()?;
+        let mut tx = conn.transaction()?;
+        for record in &records {
+            upsert(&mut tx, record)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+        let row = self
+            .connection()?
+            .query_opt(&format!("{SELECT_COLUMNS} WHERE id = $1"), &[&id])?;
+        row.as_ref().map(decode_record).transpose()
+    }
+
+    fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+        let rows = self.connection()?.query(SELECT_COLUMNS, &[])?;
+        rows.iter().map(decode_record).collect()
+    }
+
+    fn get_embeddings_page(&self, offset: usize, limit: usize) -> Result<Vec<EmbeddingRecord>> {
+        let rows = self.connection()?.query(
+            &format!("{SELECT_COLUMNS} ORDER BY created_at, id LIMIT $1 OFFSET $2"),
+            &[&to_i64(limit), &to_i64(offset)],
+        )?;
+        rows.iter().map(decode_record).collect()
+    }
+
+    fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+        Ok(self.connection()?.execute("DELETE FROM embeddings WHERE id = $1", &[&id])? > 0)
+    }
+
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        check_dimensions(Some(self.dims), [&record])?;
+        let updated = self.connection()?.execute(
+            "UPDATE embeddings
+             SET name = $2, embedding = $3, created_at = $4, metadata = $5, expires_at = $6
+             WHERE id = $1",
+            &[
+                &record.id,
+                &record.name,
+                &Vector::from(record.embedding.clone()),
+                &to_micros(record.created_at),
+                &serde_json::to_string(&record.metadata)?,
+                &record.expires_at.map(to_micros),
+            ],
+        )?;
+        if updated == 0 {
+            return Err(FaceAuthError::NotFound(record.id).into());
+        }
+        Ok(())
+    }
+
+    fn count(&self) -> Result<usize> {
+        let count: i64 = self.connection()?.query_one("SELECT COUNT(*) FROM embeddings", &[])?.get(0);
+        Ok(count as usize)
+    }
+
+    fn contains(&self, id: &str) -> Result<bool> {
+        Ok(self
+            .connection()?
+            .query_one("SELECT EXISTS(SELECT 1 FROM embeddings WHERE id = $1)", &[&id])?
+            .get(0))
+    }
+
+    // The column type fixes the dimension, but an empty table still reports none
+    fn dimension(&self) -> Result<Option<usize>> {
+        Ok((self.count()? > 0).then_some(self.dims))
+    }
+
+    fn nearest(&self, query: &[f32], limit: usize) -> Result<Option<Vec<(EmbeddingRecord, f32)>>> {
+        self.search_similar(query, limit).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+
+    // Connection string of a Postgres server with pgvector installed. Run the ignored test with
+    // `FACE_AUTH_PG_URL=... cargo test --features pgvector -- --ignored`
+    const URL_ENV: &str = "FACE_AUTH_PG_URL";
+
+    #[test]
+    #[ignore = "needs FACE_AUTH_PG_URL"]
+    fn pgvector_enrolls_and_finds_nearest() -> Result<()> {
+        let url = std::env::var(URL_ENV).expect("FACE_AUTH_PG_URL should point at a Postgres server with pgvector");
+        let mut storage = PgVectorStorage::new(&url, 3)?;
+        let alice = record("alice", vec![1.0, 0.0, 0.0]);
+        let mut bob = record("bob", vec![0.0, 1.0, 0.0]);
+        bob.expires_at = Some(bob.created_at + chrono::Duration::days(1));
+        let carol = record("carol", vec![0.7, 0.7, 0.0]);
+        storage.store_embeddings(vec![alice.clone(), bob.clone(), carol.clone()])?;
+
+        let nearest = storage.top_k(&[0.9, 0.1, 0.0], 2)?;
+        let ids: Vec<&str> = nearest.iter().map(|(r, _)| r.id.as_str()).collect();
+        assert_eq!(ids, [alice.id.as_str(), carol.id.as_str()]);
+        assert!(nearest[0].1 > nearest[1].1 && nearest[0].1 <= 1.0);
+        assert_eq!(storage.nearest(&[0.9, 0.1, 0.0], 2)?, Some(nearest));
+
+        // Read back at Postgres' microsecond resolution, so that is all the round trip keeps
+        let stored = storage.get_embedding(&bob.id)?.expect("bob was just stored");
+        bob.created_at = to_micros(bob.created_at);
+        bob.expires_at = bob.expires_at.map(to_micros);
+        assert_eq!(stored, bob);
+        assert!(storage.store_embedding(record("short", vec![1.0])).is_err());
+        assert!(matches!(
+            PgVectorStorage::new(&url, 4).err().as_ref().and_then(|e| e.downcast_ref()),
+            Some(FaceAuthError::DimensionMismatch { expected: 3, actual: 4 })
+        ));
+
+        for id in [&alice.id, &bob.id, &carol.id] {
+            assert!(storage.delete_embedding(id)?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+// Symmetric per-vector int8 quantization: each value is stored as round(v / scale)
+// with scale = max|v| / 127, cutting the size of an embedding to a quarter.
+
+pub fn quantize(embedding: &[f32]) -> (Vec<i8>, f32) {
+    let max_abs = embedding.iter().fold(0f32, |max, v| max.max(v.abs()));
+    if max_abs == 0.0 {
+        return (vec![0; embedding.len()], 0.0);
+    }
+    let scale = max_abs / 127.0;
+    let quantized = embedding
+        .iter()
+        .map(|v| (v / scale).round().clamp(-127.0, 127.0) as i8)
+        .collect();
+    (quantized, scale)
+}
+
+pub fn dequantize(quantized: &[i8], scale: f32) -> Vec<f32> {
+    quantized.iter().map(|&q| q as f32 * scale).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use ex01_image_processing_solution::imagenet::load_image224;
+    use ex02_embeddings_solution::{build_model, compute_embedding};
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    #[test]
+    fn dequantized_fixture_similarity_stays_close() -> Result<()> {
+        let model = build_model()?;
+        let mut embeddings = Vec::new();
+        for path in [
+            "../../../app/test_images/brad1.png",
+            "../../../app/test_images/brad2.png",
+            "../../../app/test_images/tom.png",
+        ] {
+            let image = load_image224(path)?;
+            embeddings.push(compute_embedding(&model, &image)?.squeeze(0)?.to_vec1::<f32>()?);
+        }
+
+        let restored: Vec<Vec<f32>> = embeddings
+            .iter()
+            .map(|e| {
+                let (q, scale) = quantize(e);
+                dequantize(&q, scale)
+            })
+            .collect();
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            let full = cosine(&embeddings[i], &embeddings[j]);
+            let approx = cosine(&restored[i], &restored[j]);
+            assert!((full - approx).abs() < 1e-2, "pair ({}, {}): {} vs {}", i, j, full, approx);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn quantize_round_trip_is_bounded() {
+        let embedding = vec![0.5, -1.27, 0.0, 0.0049, 1.0];
+        let (q, scale) = quantize(&embedding);
+        assert!((scale - 0.01).abs() < 1e-6);
+        assert_eq!(q[1], -127);
+        for (restored, original) in dequantize(&q, scale).iter().zip(&embedding) {
+            assert!((restored - original).abs() <= scale / 2.0 + 1e-6);
+        }
+
+        let (zeros, scale) = quantize(&[0.0; 4]);
+        assert_eq!(zeros, vec![0; 4]);
+        assert_eq!(dequantize(&zeros, scale), vec![0.0; 4]);
+    }
+}
@@ -0,0 +1,217 @@
+use super::{EmbeddingRecord, EmbeddingStorage, check_dimensions};
+use anyhow::{Context, Result};
+use face_auth_error::FaceAuthError;
+use redis::{Commands, Connection};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+const DEFAULT_NAMESPACE: &str = "face_auth";
+
+// Redis storage so several processes can share one gallery. Each record is a hash at
+// `{namespace}:record:{id}` and the set `{namespace}:ids` indexes them; writes update both in
+// one MULTI/EXEC. Redis does no vector search here, so retrieval still scores in Rust.
+pub struct RedisStorage {
+    conn: Mutex<Connection>,
+    namespace: String,
+}
+
+impl RedisStorage {
+    // `url` is a Redis URL such as `redis://127.0.0.1/`
+    pub fn new(url: &str) -> Result<Self> {
+        Self::with_namespace(url, DEFAULT_NAMESPACE)
+    }
+
+    // Keys are prefixed with `namespace`, so galleries can share one Redis database
+    pub fn with_namespace(url: &str, namespace: &str) -> Result<Self> {
+        let conn = redis::Client::open(url)
+            .and_then(|client| client.get_connection())
+            .map_err(|e| FaceAuthError::StorageError(Box::new(e)))
+            .with_context(|| format!("Failed to connect to Redis at {url}"))?;
+        Ok(RedisStorage {
+            conn: Mutex::new(conn),
+            namespace: namespace.to_string(),
+        })
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| FaceAuthError::StorageError("Redis connection lock poisoned".into()).into())
+    }
+
+    fn ids_key(&self) -> String {
+        format!("{}:ids", self.namespace)
+    }
+
+    fn record_key(&self, id: &str) -> String {
+        format!("{}:record:{}", self.namespace, id)
+    }
+
+    fn write(&self, records: &[EmbeddingRecord]) -> Result<()> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for record in records {
+            let key = self.record_key(&record.id);
+            // Replace the whole hash so fields cleared since the last write do not linger
+            pipe.del(&key).ignore();
+            pipe.hset_multiple(&key, &encode_record(record)?).ignore();
+            pipe.sadd(self.ids_key(), &record.id).ignore();
+        }
+        pipe.query::<()>(&mut *self.connection()?)?;
+        Ok(())
+    }
+}
+
+fn encode_record(record: &EmbeddingRecord) -> Result<Vec<(&'static str, Vec<u8>)>> {
+    let mut fields = vec![
+        ("id", record.id.clone().into_bytes()),
+        ("name", record.name.clone().into_bytes()),
+        ("embedding", record.embedding.iter().flat_map(|v| v.to_le_bytes()).collect()),
+        ("created_at", record.created_at.to_rfc3339().into_bytes()),
+        ("metadata", serde_json::to_vec(&record.metadata)?),
+    ];
+    if let Some(expires_at) = record.expires_at {
+        fields.push(("expires_at", expires_at.to_rfc3339().into_bytes()));
+    }
+    Ok(fields)
+}
+
+fn decode_record(mut fields: HashMap<String, Vec<u8>>) -> Result<EmbeddingRecord> {
+    let mut take = |name: &str| fields.remove(name).with_context(|| format!("Redis record is missing '{name}'"));
+    let text = |bytes: Vec<u8>| String::from_utf8(bytes).context("Redis record field is not UTF-8");
+    let timestamp = |bytes: Vec<u8>| -> Result<chrono::DateTime<chrono::Utc>> {
+        Ok(chrono::DateTime::parse_from_rfc3339(&text(bytes)?)?.with_timezone(&chrono::Utc))
+    };
+
+    let id = text(take("id")?)?;
+    let name = text(take("name")?)?;
+    let embedding = take("embedding")?
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    let created_at = timestamp(take("created_at")?)?;
+    let metadata = serde_json::from_slice(&take("metadata")?)?;
+    let expires_at = take("expires_at").ok().map(timestamp).transpose()?;
+    Ok(EmbeddingRecord {
+        id,
+        name,
+        embedding,
+        created_at,
+        metadata,
+        expires_at,
+    })
+}
+
+impl EmbeddingStorage for RedisStorage {
+    fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        check_dimensions(self.dimension()?, [&record])?;
+        self.write(&[record])
+    }
+
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        check_dimensions(self.dimension()?, &records)?;
+        self.write(&records)
+    }
+
+    fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+        let fields: HashMap<String, Vec<u8>> = self.connection()?.hgetall(self.record_key(id))?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        decode_record(fields).map(Some)
+    }
+
+    // One round trip for the ids, one pipelined round trip for every hash
+    fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+        let mut conn = self.connection()?;
+        let ids: Vec<String> = conn.smembers(self.ids_key())?;
+        let mut pipe = redis::pipe();
+        for id in &ids {
+            pipe.hgetall(self.record_key(id));
+        }
+        let hashes: Vec<HashMap<String, Vec<u8>>> = pipe.query(&mut *conn)?;
+        hashes
+            .into_iter()
+            .filter(|fields| !fields.is_empty())
+            .map(decode_record)
+            .collect()
+    }
+
+    fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+        let (deleted, _): (i64, i64) = redis::pipe()
+            .atomic()
+            .del(self.record_key(id))
+            .srem(self.ids_key(), id)
+            .query(&mut *self.connection()?)?;
+        Ok(deleted > 0)
+    }
+
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        check_dimensions(self.dimension()?, [&record])?;
+        if !self.contains(&record.id)? {
+            return Err(FaceAuthError::NotFound(record.id).into());
+        }
+        self.write(&[record])
+    }
+
+    fn count(&self) -> Result<usize> {
+        Ok(self.connection()?.scard(self.ids_key())?)
+    }
+
+    fn contains(&self, id: &str) -> Result<bool> {
+        Ok(self.connection()?.sismember(self.ids_key(), id)?)
+    }
+
+    // Embeddings are stored as little-endian f32s, so any one record gives the length
+    fn dimension(&self) -> Result<Option<usize>> {
+        let mut conn = self.connection()?;
+        let Some(id) = conn.srandmember::<_, Option<String>>(self.ids_key())? else {
+            return Ok(None);
+        };
+        let bytes: Option<Vec<u8>> = conn.hget(self.record_key(&id), "embedding")?;
+        Ok(bytes.map(|bytes| bytes.len() / 4))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::record;
+    use uuid::Uuid;
+
+    // URL of a running Redis server. Run the ignored test with
+    // `FACE_AUTH_REDIS_URL=... cargo test --features redis -- --ignored`
+    const URL_ENV: &str = "FACE_AUTH_REDIS_URL";
+
+    #[test]
+    #[ignore = "needs FACE_AUTH_REDIS_URL"]
+    fn second_client_sees_records_from_the_first() -> Result<()> {
+        let url = std::env::var(URL_ENV).expect("FACE_AUTH_REDIS_URL should point at a running Redis server");
+        let namespace = format!("face_auth_test_{}", Uuid::new_v4());
+        let mut writer = RedisStorage::with_namespace(&url, &namespace)?;
+        let mut reader = RedisStorage::with_namespace(&url, &namespace)?;
+
+        let mut alice = record("alice", vec![0.25, -0.5, 1.0]);
+        alice.expires_at = Some(alice.created_at + chrono::Duration::days(1));
+        let bob = record("bob", vec![1.0, 0.0, 0.0]);
+        writer.store_embeddings(vec![alice.clone(), bob.clone()])?;
+
+        assert_eq!(reader.count()?, 2);
+        assert_eq!(reader.dimension()?, Some(3));
+        // Timestamps round-trip through RFC 3339, which keeps full precision
+        assert_eq!(reader.get_embedding(&alice.id)?, Some(alice.clone()));
+        let mut all = reader.get_all_embeddings()?;
+        all.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut expected = vec![alice.clone(), bob.clone()];
+        expected.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(all, expected);
+        assert!(writer.store_embedding(record("short", vec![1.0])).is_err());
+
+        assert!(reader.delete_embedding(&alice.id)?);
+        assert!(!writer.contains(&alice.id)?);
+        assert!(writer.update_embedding(alice).is_err());
+        assert!(writer.delete_embedding(&bob.id)?);
+        assert_eq!(reader.count()?, 0);
+        Ok(())
+    }
+}
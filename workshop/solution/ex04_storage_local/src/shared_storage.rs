@@ -0,0 +1,111 @@
+use super::{EmbeddingRecord, EmbeddingStorage};
+use anyhow::Result;
+use face_auth_error::FaceAuthError;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// Cheaply cloneable handle to one storage shared across threads; every clone sees the same
+// records. Reads share a lock and run in parallel, writes take it exclusively. Every call is
+// forwarded whole, so e.g. `soft_delete` reads and rewrites a record under one write lock.
+pub struct SharedStorage<S: EmbeddingStorage> {
+    inner: Arc<RwLock<S>>,
+}
+
+impl<S: EmbeddingStorage> Clone for SharedStorage<S> {
+    fn clone(&self) -> Self {
+        SharedStorage {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<S: EmbeddingStorage> SharedStorage<S> {
+    pub fn new(storage: S) -> Self {
+        SharedStorage {
+            inner: Arc::new(RwLock::new(storage)),
+        }
+    }
+
+    fn read(&self) -> Result<RwLockReadGuard<'_, S>> {
+        self.inner.read().map_err(|_| poisoned().into())
+    }
+
+    fn write(&self) -> Result<RwLockWriteGuard<'_, S>> {
+        self.inner.write().map_err(|_| poisoned().into())
+    }
+}
+
+fn poisoned() -> FaceAuthError {
+    FaceAuthError::StorageError("Storage lock poisoned by a panicked writer".into())
+}
+
+impl<S: EmbeddingStorage> EmbeddingStorage for SharedStorage<S> {
+    fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        self.write()?.store_embedding(record)
+    }
+
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        self.write()?.store_embeddings(records)
+    }
+
+    fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+        self.read()?.get_embedding(id)
+    }
+
+    fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+        self.read()?.get_all_embeddings()
+    }
+
+    // The guard cannot outlive this call, so the inner stream is drained under one read lock;
+    // the result is a consistent snapshot even while other clones write
+    fn iter_embeddings(&self) -> Box<dyn Iterator<Item = Result<EmbeddingRecord>> + '_> {
+        let records: Result<Vec<_>> = self.read().and_then(|storage| storage.iter_embeddings().collect());
+        match records {
+            Ok(records) => Box::new(records.into_iter().map(Ok)),
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    }
+
+    fn get_embeddings_page(&self, offset: usize, limit: usize) -> Result<Vec<EmbeddingRecord>> {
+        self.read()?.get_embeddings_page(offset, limit)
+    }
+
+    fn count(&self) -> Result<usize> {
+        self.read()?.count()
+    }
+
+    fn contains(&self, id: &str) -> Result<bool> {
+        self.read()?.contains(id)
+    }
+
+    fn dimension(&self) -> Result<Option<usize>> {
+        self.read()?.dimension()
+    }
+
+    fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+        self.write()?.delete_embedding(id)
+    }
+
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        self.write()?.update_embedding(record)
+    }
+
+    fn nearest(&self, query: &[f32], limit: usize) -> Result<Option<Vec<(EmbeddingRecord, f32)>>> {
+        self.read()?.nearest(query, limit)
+    }
+
+    fn purge_expired(&mut self) -> Result<usize> {
+        self.write()?.purge_expired()
+    }
+
+    fn soft_delete(&mut self, id: &str) -> Result<bool> {
+        self.write()?.soft_delete(id)
+    }
+
+    fn restore(&mut self, id: &str) -> Result<bool> {
+        self.write()?.restore(id)
+    }
+
+    fn purge_deleted(&mut self) -> Result<usize> {
+        self.write()?.purge_deleted()
+    }
+}
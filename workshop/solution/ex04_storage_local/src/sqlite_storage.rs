@@ -0,0 +1,569 @@
+use super::quantize::{dequantize, quantize};
+use super::{EmbeddingRecord, EmbeddingStorage, check_dimensions};
+use anyhow::Result;
+use face_auth_error::FaceAuthError;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+// SQLite-backed storage: one row per record, embedding stored as a little-endian f32 BLOB,
+// or as int8 plus a `scale` when quantization is enabled. `Connection` is not `Sync`, so it sits
+// behind a mutex: reads through `&self` take turns, writes through `&mut self` skip the lock.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+    quantized: bool,
+}
+
+// id, name, embedding, scale, created_at, metadata, expires_at
+type RawRecord = (String, String, Vec<u8>, Option<f64>, String, String, Option<String>);
+
+impl SqliteStorage {
+    pub fn new(file_path: &str) -> Result<Self> {
+        Self::open(file_path, false)
+    }
+
+    // Like `new`, but embeddings written from now on are stored int8-quantized (4x smaller).
+    // Rows are decoded according to their own format, so both kinds can be read back.
+    pub fn new_quantized(file_path: &str) -> Result<Self> {
+        Self::open(file_path, true)
+    }
+
+    // A panic mid-statement leaves SQLite itself consistent, so a poisoned lock is still usable
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn open(file_path: &str, quantized: bool) -> Result<Self> {
+        let conn = Connection::open(file_path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS embeddings (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                scale REAL,
+                created_at TEXT NOT NULL,
+                metadata TEXT NOT NULL,
+                expires_at TEXT
+            )",
+        )?;
+        // Databases created before quantization or expiry support lack those columns
+        add_missing_column(&conn, "scale", "REAL")?;
+        add_missing_column(&conn, "expires_at", "TEXT")?;
+        Ok(SqliteStorage { conn: Mutex::new(conn), quantized })
+    }
+
+    fn record_from_row(row: &Row) -> rusqlite::Result<RawRecord> {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?))
+    }
+
+    fn decode_record((id, name, embedding, scale, created_at, metadata, expires_at): RawRecord) -> Result<EmbeddingRecord> {
+        let embedding = match scale {
+            Some(scale) => {
+                let quantized: Vec<i8> = embedding.iter().map(|&b| b as i8).collect();
+                dequantize(&quantized, scale as f32)
+            }
+            None => embedding
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        };
+        let created_at = parse_timestamp(&created_at)?;
+        let metadata: HashMap<String, String> = serde_json::from_str(&metadata)?;
+        let expires_at = expires_at.as_deref().map(parse_timestamp).transpose()?;
+        Ok(EmbeddingRecord { id, name, embedding, created_at, metadata, expires_at })
+    }
+}
+
+const SELECT_COLUMNS: &str = "SELECT id, name, embedding, scale, created_at, metadata, expires_at FROM embeddings";
+// Rows fetched per query while streaming with `iter_embeddings`
+const STREAM_PAGE_SIZE: i64 = 256;
+
+// Walks the table in rowid order one page at a time, so only a page of rows is held in memory.
+// The connection is locked per page, not for the whole walk.
+struct PagedRecords<'a> {
+    conn: &'a SqliteStorage,
+    last_rowid: i64,
+    page: VecDeque<Result<EmbeddingRecord>>,
+    exhausted: bool,
+}
+
+impl PagedRecords<'_> {
+    fn fetch_page(&mut self) -> Result<()> {
+        let conn = self.conn.conn();
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT rowid, id, name, embedding, scale, created_at, metadata, expires_at FROM embeddings
+             WHERE rowid > ?1 ORDER BY rowid LIMIT {STREAM_PAGE_SIZE}"
+        ))?;
+        let rows = stmt.query_map(params![self.last_rowid], |row| {
+            let rowid: i64 = row.get(0)?;
+            let raw = (row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?, row.get(7)?);
+            Ok((rowid, raw))
+        })?;
+        let mut fetched = 0;
+        for row in rows {
+            let (rowid, raw) = row?;
+            self.last_rowid = rowid;
+            self.page.push_back(SqliteStorage::decode_record(raw));
+            fetched += 1;
+        }
+        self.exhausted = fetched < STREAM_PAGE_SIZE;
+        Ok(())
+    }
+}
+
+impl Iterator for PagedRecords<'_> {
+    type Item = Result<EmbeddingRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.is_empty()
+            && !self.exhausted
+            && let Err(e) = self.fetch_page()
+        {
+            self.exhausted = true;
+            return Some(Err(e));
+        }
+        self.page.pop_front()
+    }
+}
+
+fn add_missing_column(conn: &Connection, column: &str, declaration: &str) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('embeddings') WHERE name = ?1",
+        params![column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE embeddings ADD COLUMN {column} {declaration}"))?;
+    }
+    Ok(())
+}
+
+fn format_timestamp(timestamp: &chrono::DateTime<chrono::Utc>) -> String {
+    timestamp.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+}
+
+fn parse_timestamp(text: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    Ok(chrono::DateTime::parse_from_rfc3339(text)?.with_timezone(&chrono::Utc))
+}
+
+// The BLOB to store and, for quantized rows, the scale to dequantize it with
+fn encode_embedding(embedding: &[f32], quantized: bool) -> (Vec<u8>, Option<f64>) {
+    if quantized {
+        let (values, scale) = quantize(embedding);
+        (values.iter().map(|&v| v as u8).collect(), Some(scale as f64))
+    } else {
+        (embedding.iter().flat_map(|v| v.to_le_bytes()).collect(), None)
+    }
+}
+
+// Quantized rows hold one byte per value, full-precision rows four
+fn stored_dimension(conn: &Connection) -> Result<Option<usize>> {
+    let row = conn
+        .query_row(
+            "SELECT length(embedding), scale IS NOT NULL FROM embeddings LIMIT 1",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?)),
+        )
+        .optional()?;
+    Ok(row.map(|(bytes, quantized)| if quantized { bytes as usize } else { bytes as usize / 4 }))
+}
+
+fn upsert(conn: &Connection, record: &EmbeddingRecord, quantized: bool) -> Result<()> {
+    let (embedding, scale) = encode_embedding(&record.embedding, quantized);
+    conn.execute(
+        "INSERT INTO embeddings (id, name, embedding, scale, created_at, metadata, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name,
+            embedding = excluded.embedding,
+            scale = excluded.scale,
+            created_at = excluded.created_at,
+            metadata = excluded.metadata,
+            expires_at = excluded.expires_at",
+        params![
+            record.id,
+            record.name,
+            embedding,
+            scale,
+            format_timestamp(&record.created_at),
+            serde_json::to_string(&record.metadata)?,
+            record.expires_at.as_ref().map(format_timestamp),
+        ],
+    )?;
+    Ok(())
+}
+
+impl EmbeddingStorage for SqliteStorage {
+    fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        let conn = self.conn.get_mut().unwrap_or_else(PoisonError::into_inner);
+        check_dimensions(stored_dimension(conn)?, [&record])?;
+        upsert(conn, &record, self.quantized)
+    }
+
+    // The batch runs in one transaction, so a failure midway leaves the table untouched
+    fn store_embeddings(&mut self, records: Vec<EmbeddingRecord>) -> Result<()> {
+        let conn = self.conn.get_mut().unwrap_or_else(PoisonError::into_inner);
+        check_dimensions(stored_dimension(conn)?, &records)?;
+        let tx = conn.transaction()?;
+        for record in &records {
+            upsert(&tx, record, self.quantized)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+        let row = self
+            .conn()
+            .query_row(
+                &format!("{SELECT_COLUMNS} WHERE id = ?1"),
+                params![id],
+                Self::record_from_row,
+            )
+            .optional()?;
+        row.map(Self::decode_record).transpose()
+    }
+
+    fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(SELECT_COLUMNS)?;
+        // Rows are decoded one at a time as SQLite steps through the result set
+        let rows = stmt.query_map([], Self::record_from_row)?;
+        rows.map(|row| Self::decode_record(row?)).collect()
+    }
+
+    fn iter_embeddings(&self) -> Box<dyn Iterator<Item = Result<EmbeddingRecord>> + '_> {
+        Box::new(PagedRecords {
+            conn: self,
+            last_rowid: i64::MIN,
+            page: VecDeque::new(),
+            exhausted: false,
+        })
+    }
+
+    // Timestamps are stored in a fixed-width RFC 3339 form, so text order is time order
+    fn get_embeddings_page(&self, offset: usize, limit: usize) -> Result<Vec<EmbeddingRecord>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!("{SELECT_COLUMNS} ORDER BY created_at, id LIMIT ?1 OFFSET ?2"))?;
+        let rows = stmt.query_map(
+            params![i64::try_from(limit).unwrap_or(i64::MAX), i64::try_from(offset).unwrap_or(i64::MAX)],
+            Self::record_from_row,
+        )?;
+        rows.map(|row| Self::decode_record(row?)).collect()
+    }
+
+    fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+        let conn = self.conn.get_mut().unwrap_or_else(PoisonError::into_inner);
+        let deleted = conn.execute("DELETE FROM embeddings WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+        let conn = self.conn.get_mut().unwrap_or_else(PoisonError::into_inner);
+        check_dimensions(stored_dimension(conn)?, [&record])?;
+        let (embedding, scale) = encode_embedding(&record.embedding, self.quantized);
+        let updated = conn.execute(
+            "UPDATE embeddings
+             SET name = ?2, embedding = ?3, scale = ?4, created_at = ?5, metadata = ?6, expires_at = ?7
+             WHERE id = ?1",
+            params![
+                record.id,
+                record.name,
+                embedding,
+                scale,
+                format_timestamp(&record.created_at),
+                serde_json::to_string(&record.metadata)?,
+                record.expires_at.as_ref().map(format_timestamp),
+            ],
+        )?;
+        if updated == 0 {
+            return Err(FaceAuthError::NotFound(record.id).into());
+        }
+        Ok(())
+    }
+
+    fn count(&self) -> Result<usize> {
+        let count: i64 = self.conn().query_row("SELECT COUNT(*) FROM embeddings", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    fn contains(&self, id: &str) -> Result<bool> {
+        Ok(self.conn().query_row(
+            "SELECT EXISTS(SELECT 1 FROM embeddings WHERE id = ?1)",
+            params![id],
+            |row| row.get(0),
+        )?)
+    }
+
+    fn dimension(&self) -> Result<Option<usize>> {
+        stored_dimension(&self.conn())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{TempFileGuard, record};
+    use uuid::Uuid;
+
+    #[test]
+    fn sqlite_records_survive_reopen() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+
+        let records = vec![
+            record("alice", vec![0.1, 0.2, 0.3]),
+            record("bob", vec![-1.0, 0.5, 2.25]),
+            record("carol", vec![0.0; 3]),
+        ];
+        {
+            let mut storage = SqliteStorage::new(&path)?;
+            for r in &records {
+                storage.store_embedding(r.clone())?;
+            }
+        }
+
+        let storage = SqliteStorage::new(&path)?;
+        let all = storage.get_all_embeddings()?;
+        assert_eq!(all.len(), 3);
+        for r in &records {
+            assert_eq!(storage.get_embedding(&r.id)?.as_ref(), Some(r));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_store_upserts_by_id() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let mut storage = SqliteStorage::new(&path)?;
+
+        let mut r = record("alice", vec![1.0, 0.0]);
+        storage.store_embedding(r.clone())?;
+        r.embedding = vec![0.0, 1.0];
+        storage.store_embedding(r.clone())?;
+
+        let all = storage.get_all_embeddings()?;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].embedding, vec![0.0, 1.0]);
+        r.name = "alicia".to_string();
+        storage.update_embedding(r.clone())?;
+        assert_eq!(storage.get_embedding(&r.id)?.map(|r| r.name), Some("alicia".to_string()));
+        assert!(storage.update_embedding(record("nobody", vec![1.0, 0.0])).is_err());
+
+        assert!(storage.delete_embedding(&r.id)?);
+        assert!(!storage.delete_embedding(&r.id)?);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_batch_store_is_transactional() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let mut storage = SqliteStorage::new(&path)?;
+
+        let batch: Vec<_> = (0..5).map(|i| record(&format!("person_{i}"), vec![i as f32, 1.0])).collect();
+        storage.store_embeddings(batch.clone())?;
+        assert_eq!(storage.get_all_embeddings()?.len(), 5);
+
+        // A failing statement midway rolls back the records stored before it
+        storage.conn().execute_batch(
+            "CREATE TRIGGER reject_mallory BEFORE INSERT ON embeddings
+             WHEN NEW.name = 'mallory' BEGIN SELECT RAISE(ABORT, 'rejected'); END",
+        )?;
+        let failing = vec![record("dave", vec![1.0, 0.0]), record("mallory", vec![0.0, 1.0])];
+        assert!(storage.store_embeddings(failing).is_err());
+        assert_eq!(storage.get_all_embeddings()?.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_quantized_rows_round_trip() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+
+        let full = record("alice", vec![0.1, -0.2, 0.3]);
+        SqliteStorage::new(&path)?.store_embedding(full.clone())?;
+        let mut storage = SqliteStorage::new_quantized(&path)?;
+        let small = record("bob", vec![-1.0, 0.5, 0.25]);
+        storage.store_embedding(small.clone())?;
+
+        // Full-precision rows written earlier are still read back exactly
+        assert_eq!(storage.get_embedding(&full.id)?, Some(full));
+        let restored = storage.get_embedding(&small.id)?.expect("bob should exist");
+        for (a, b) in restored.embedding.iter().zip(&small.embedding) {
+            assert!((a - b).abs() < 1e-2, "{} vs {}", a, b);
+        }
+        let blob_len: i64 = storage.conn().query_row(
+            "SELECT length(embedding) FROM embeddings WHERE id = ?1",
+            params![small.id],
+            |row| row.get(0),
+        )?;
+        assert_eq!(blob_len, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_rejects_mismatched_dimension() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let mut storage = SqliteStorage::new(&path)?;
+        assert_eq!(storage.dimension()?, None);
+
+        let mut alice = record("alice", vec![0.5; 16]);
+        storage.store_embedding(alice.clone())?;
+        assert_eq!(SqliteStorage::new_quantized(&path)?.dimension()?, Some(16));
+
+        let err = storage.store_embedding(record("bob", vec![0.5; 8])).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FaceAuthError>(),
+            Some(FaceAuthError::DimensionMismatch { expected: 16, actual: 8 })
+        ));
+        alice.embedding = vec![0.5; 4];
+        assert!(storage.update_embedding(alice).is_err());
+        assert!(storage.store_embeddings(vec![record("carol", vec![0.5; 3])]).is_err());
+        assert_eq!(storage.get_all_embeddings()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_streams_records_across_pages() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let mut storage = SqliteStorage::new(&path)?;
+
+        let count = STREAM_PAGE_SIZE as usize * 2 + 7;
+        storage.store_embeddings((0..count).map(|i| record(&format!("person_{i}"), vec![i as f32, 1.0])).collect())?;
+
+        let mut streamed = storage.iter_embeddings().collect::<Result<Vec<_>>>()?;
+        assert_eq!(streamed.len(), count);
+        let mut all = storage.get_all_embeddings()?;
+        all.sort_by(|a, b| a.id.cmp(&b.id));
+        streamed.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(streamed, all);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_adds_expiry_column_to_old_databases() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        Connection::open(&path)?.execute_batch(
+            "CREATE TABLE embeddings (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                scale REAL,
+                created_at TEXT NOT NULL,
+                metadata TEXT NOT NULL
+            )",
+        )?;
+
+        let mut storage = SqliteStorage::new(&path)?;
+        let mut visitor = record("visitor", vec![1.0, 0.0]);
+        visitor.expires_at = Some(chrono::Utc::now() - chrono::Duration::hours(1));
+        let staff = record("staff", vec![0.0, 1.0]);
+        storage.store_embeddings(vec![visitor.clone(), staff.clone()])?;
+        drop(storage);
+
+        let mut reopened = SqliteStorage::new(&path)?;
+        assert_eq!(reopened.get_embedding(&visitor.id)?, Some(visitor));
+        assert_eq!(reopened.purge_expired()?, 1);
+        assert_eq!(reopened.get_all_embeddings()?, vec![staff]);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_opens_databases_from_before_quantization() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        // The original schema: no `scale` and no `expires_at`
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            "CREATE TABLE embeddings (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                created_at TEXT NOT NULL,
+                metadata TEXT NOT NULL
+            )",
+        )?;
+        let old = record("old", vec![0.5, -0.25]);
+        let blob: Vec<u8> = old.embedding.iter().flat_map(|v| v.to_le_bytes()).collect();
+        conn.execute(
+            "INSERT INTO embeddings (id, name, embedding, created_at, metadata) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![old.id, old.name, blob, format_timestamp(&old.created_at), serde_json::to_string(&old.metadata)?],
+        )?;
+        drop(conn);
+
+        let mut storage = SqliteStorage::new_quantized(&path)?;
+        assert_eq!(storage.get_embedding(&old.id)?, Some(old.clone()));
+        let new = record("new", vec![1.0, 0.0]);
+        storage.store_embedding(new.clone())?;
+        assert_eq!(storage.get_all_embeddings()?.len(), 2);
+        assert!(storage.get_embedding(&new.id)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn pages_cover_every_record_once_in_both_backends() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let mut sqlite = SqliteStorage::new(&path)?;
+        let mut memory = crate::InMemoryStorage::new();
+
+        // Pairs share a timestamp, so ties have to be broken by id
+        let start = chrono::Utc::now();
+        let records: Vec<EmbeddingRecord> = (0..10)
+            .map(|i| {
+                let mut r = record(&format!("person_{i}"), vec![i as f32, 1.0]);
+                r.created_at = start + chrono::Duration::seconds(i / 2);
+                r
+            })
+            .collect();
+        sqlite.store_embeddings(records.clone())?;
+        memory.store_embeddings(records.clone())?;
+
+        let mut expected = records;
+        expected.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        let storages: [&dyn EmbeddingStorage; 2] = [&sqlite, &memory];
+        for storage in storages {
+            let (first, second) = (storage.get_embeddings_page(0, 5)?, storage.get_embeddings_page(5, 5)?);
+            assert_eq!((first.len(), second.len()), (5, 5));
+            assert_eq!([first, second].concat(), expected);
+            assert!(storage.get_embeddings_page(10, 5)?.is_empty());
+            assert!(storage.get_embeddings_page(usize::MAX, 5)?.is_empty());
+            assert_eq!(storage.get_embeddings_page(8, 5)?.len(), 2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn count_and_contains_track_inserts_and_deletes() -> Result<()> {
+        let path = format!("workshop_sqlite_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let (local, local_path) = crate::open_temp_storage()?;
+        let _local_guard = TempFileGuard { path: local_path };
+        let storages: Vec<Box<dyn EmbeddingStorage>> =
+            vec![Box::new(SqliteStorage::new(&path)?), Box::new(crate::InMemoryStorage::new()), local];
+
+        for mut storage in storages {
+            assert_eq!(storage.count()?, 0);
+            let alice = record("alice", vec![1.0, 0.0]);
+            let bob = record("bob", vec![0.0, 1.0]);
+            assert!(!storage.contains(&alice.id)?);
+            storage.store_embeddings(vec![alice.clone(), bob.clone()])?;
+            assert_eq!(storage.count()?, 2);
+            assert!(storage.contains(&alice.id)? && storage.contains(&bob.id)?);
+
+            // Re-storing an id replaces it rather than adding a record
+            storage.store_embedding(alice.clone())?;
+            assert_eq!(storage.count()?, 2);
+            assert!(storage.delete_embedding(&alice.id)?);
+            assert_eq!(storage.count()?, 1);
+            assert!(!storage.contains(&alice.id)?);
+            assert!(!storage.contains("missing")?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::average_normalized;
+use anyhow::Result;
+use chrono::Utc;
+use face_auth_error::FaceAuthError;
+use ex03_similarity_solution::cosine_similarity_vec;
+use ex04_storage_local_solution::{EmbeddingRecord, EmbeddingStorage};
+
+// Every pair of live records whose cosine similarity exceeds `threshold`, as (id_a, id_b, similarity),
+// most similar first; expired and soft-deleted records are left out like they are from search.
+// Compares all pairs, so this is meant for offline gallery maintenance.
+pub fn find_duplicates(storage: &dyn EmbeddingStorage, threshold: f32) -> Result<Vec<(String, String, f32)>> {
+    let now = Utc::now();
+    let mut records: Vec<_> = storage
+        .get_all_embeddings()?
+        .into_iter()
+        .filter(|record| !record.is_expired_at(now) && !record.is_deleted())
+        .collect();
+    records.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut duplicates = Vec::new();
+    for (i, a) in records.iter().enumerate() {
+        for b in &records[i + 1..] {
+            let similarity = cosine_similarity_vec(&a.embedding, &b.embedding)?;
+            if similarity > threshold {
+                duplicates.push((a.id.clone(), b.id.clone(), similarity));
+            }
+        }
+    }
+    duplicates.sort_by(|a, b| b.2.total_cmp(&a.2));
+    Ok(duplicates)
+}
+
+// Fold `drop_id` into `keep_id`: the kept record gets the re-normalized mean of both embeddings
+// and the other record is deleted. Returns the updated record. Soft-deleted records are refused:
+// restore one first rather than folding a retired embedding back into the gallery.
+pub fn merge(storage: &mut dyn EmbeddingStorage, keep_id: &str, drop_id: &str) -> Result<EmbeddingRecord> {
+    if keep_id == drop_id {
+        anyhow::bail!("Cannot merge record '{}' into itself", keep_id);
+    }
+    let Some(mut keep) = storage.get_embedding(keep_id)? else {
+        return Err(FaceAuthError::NotFound(keep_id.to_string()).into());
+    };
+    let Some(drop) = storage.get_embedding(drop_id)? else {
+        return Err(FaceAuthError::NotFound(drop_id.to_string()).into());
+    };
+    if let Some(deleted) = [&keep, &drop].into_iter().find(|record| record.is_deleted()) {
+        anyhow::bail!("Cannot merge soft-deleted record '{}'", deleted.id);
+    }
+
+    // Templates from `enroll_identity` track how many photos they were built from
+    let source_images = source_images(&keep) + source_images(&drop);
+    keep.embedding = average_normalized(&[keep.embedding, drop.embedding])?;
+    keep.metadata.insert("source_images".to_string(), source_images.to_string());
+
+    storage.update_embedding(keep.clone())?;
+    storage.delete_embedding(drop_id)?;
+    Ok(keep)
+}
+
+fn source_images(record: &EmbeddingRecord) -> usize {
+    record
+        .metadata
+        .get("source_images")
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_record;
+    use ex04_storage_local_solution::{open_temp_storage, TempFileGuard};
+
+    #[test]
+    fn same_face_enrolled_twice_is_flagged() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        let first = add_record(storage.as_mut(), "brad", vec![0.6, 0.8, 0.0])?;
+        let second = add_record(storage.as_mut(), "brad", vec![0.61, 0.79, 0.01])?;
+        add_record(storage.as_mut(), "tom", vec![0.0, 0.3, 1.0])?;
+
+        let duplicates = find_duplicates(storage.as_ref(), 0.99)?;
+        assert_eq!(duplicates.len(), 1);
+        let (a, b, similarity) = &duplicates[0];
+        let mut flagged = [a.clone(), b.clone()];
+        flagged.sort();
+        let mut expected = [first, second];
+        expected.sort();
+        assert_eq!(flagged, expected);
+        assert!(*similarity > 0.99);
+        assert!(find_duplicates(storage.as_ref(), 1.0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn inactive_records_are_neither_flagged_nor_merged() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        let live = add_record(storage.as_mut(), "brad", vec![0.6, 0.8])?;
+        let deleted = add_record(storage.as_mut(), "brad", vec![0.61, 0.79])?;
+        let expired = add_record(storage.as_mut(), "brad", vec![0.6, 0.81])?;
+        storage.soft_delete(&deleted)?;
+        let mut record = storage.get_embedding(&expired)?.expect("record was just stored");
+        record.expires_at = Some(Utc::now() - chrono::Duration::minutes(1));
+        storage.update_embedding(record)?;
+
+        assert!(find_duplicates(storage.as_ref(), 0.99)?.is_empty());
+        assert!(merge(storage.as_mut(), &live, &deleted).is_err());
+        assert!(merge(storage.as_mut(), &deleted, &live).is_err());
+        assert!(storage.get_embedding(&deleted)?.is_some(), "A refused merge deletes nothing");
+        Ok(())
+    }
+
+    #[test]
+    fn merge_averages_into_kept_record() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        let keep = add_record(storage.as_mut(), "brad", vec![2.0, 0.0])?;
+        let drop = add_record(storage.as_mut(), "brad", vec![0.0, 1.0])?;
+        assert!(merge(storage.as_mut(), &keep, &keep).is_err());
+
+        let merged = merge(storage.as_mut(), &keep, &drop)?;
+        let expected = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(merged.embedding.iter().all(|v| (v - expected).abs() < 1e-6));
+        assert_eq!(merged.metadata.get("source_images").map(String::as_str), Some("2"));
+        assert_eq!(storage.get_embedding(&keep)?, Some(merged));
+        assert!(storage.get_embedding(&drop)?.is_none());
+        assert!(merge(storage.as_mut(), &keep, &drop).is_err());
+        Ok(())
+    }
+}
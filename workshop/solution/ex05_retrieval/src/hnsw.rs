@@ -0,0 +1,541 @@
+use anyhow::Result;
+use ex03_similarity_solution::normalize_l2_vec;
+use ex04_storage_local_solution::{EmbeddingRecord, EmbeddingStorage, IndexObserver};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+const DEFAULT_M: usize = 16;
+const DEFAULT_EF_CONSTRUCTION: usize = 200;
+const DEFAULT_EF_SEARCH: usize = 64;
+// Level seed used unless one is passed to `with_seed`
+const LEVEL_SEED: u64 = 0x5eed_f00d;
+// The graph is rebuilt from its live nodes once more than 1 in TOMBSTONE_DIVISOR nodes is removed
+const TOMBSTONE_DIVISOR: usize = 4;
+
+// A node id scored by cosine similarity to the current query, ordered by similarity
+#[derive(Clone, Copy, PartialEq)]
+struct Scored(f32, usize);
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then_with(|| other.1.cmp(&self.1))
+    }
+}
+
+struct Node {
+    id: String,
+    // L2-normalized, so cosine similarity is a plain dot product
+    vector: Vec<f32>,
+    // neighbours[level] holds the links of this node on that layer
+    neighbours: Vec<Vec<usize>>,
+    // Removed nodes stay in the graph as waypoints but are never returned
+    deleted: bool,
+}
+
+/// Approximate nearest-neighbour index over embeddings (Hierarchical Navigable Small World graph).
+///
+/// Built once from an `EmbeddingStorage` and grown with `insert`; `search` answers
+/// top-k cosine similarity queries without scanning every record. Attached to an
+/// `ObservedStorage` (as an `IndexObserver`) it follows inserts and deletes automatically.
+pub struct HnswIndex {
+    nodes: Vec<Node>,
+    // Live ids only
+    positions: HashMap<String, usize>,
+    tombstones: usize,
+    entry_point: Option<usize>,
+    max_level: usize,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    level_multiplier: f64,
+    rng: StdRng,
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::with_params(DEFAULT_M, DEFAULT_EF_CONSTRUCTION, DEFAULT_EF_SEARCH)
+    }
+}
+
+impl HnswIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `m` is the number of links per node (doubled on the bottom layer),
+    /// `ef_construction` / `ef_search` the candidate list sizes used when building and querying.
+    pub fn with_params(m: usize, ef_construction: usize, ef_search: usize) -> Self {
+        let m = m.max(2);
+        HnswIndex {
+            nodes: Vec::new(),
+            positions: HashMap::new(),
+            tombstones: 0,
+            entry_point: None,
+            max_level: 0,
+            m,
+            ef_construction: ef_construction.max(1),
+            ef_search: ef_search.max(1),
+            level_multiplier: 1.0 / (m as f64).ln(),
+            rng: StdRng::seed_from_u64(LEVEL_SEED),
+        }
+    }
+
+    // Default parameters with a caller-chosen level seed; the same seed and insertion order
+    // always build the same graph
+    pub fn with_seed(seed: u64) -> Self {
+        HnswIndex {
+            rng: StdRng::seed_from_u64(seed),
+            ..Self::new()
+        }
+    }
+
+    // Build an index over every searchable record currently in the storage: soft-deleted and
+    // expired records are left out, as brute-force search leaves them out
+    pub fn from_storage(storage: &dyn EmbeddingStorage) -> Result<Self> {
+        let mut index = Self::new();
+        let now = chrono::Utc::now();
+        for record in storage.get_all_embeddings()? {
+            if record.is_deleted() || record.is_expired_at(now) {
+                continue;
+            }
+            index.insert(&record.id, &record.embedding)?;
+        }
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // Drop `id` from results, returning whether it was indexed. Its node keeps its links so the
+    // graph stays navigable; re-inserting the id adds a fresh node. Once removed nodes make up
+    // too large a share of the graph, it is rebuilt from the live ones.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let Some(node) = self.positions.remove(id) else {
+            return false;
+        };
+        self.nodes[node].deleted = true;
+        self.tombstones += 1;
+        if self.tombstones * TOMBSTONE_DIVISOR > self.nodes.len() {
+            self.compact();
+        }
+        true
+    }
+
+    pub fn insert(&mut self, id: &str, embedding: &[f32]) -> Result<()> {
+        self.check_embedding(id, embedding)?;
+        if self.positions.contains_key(id) {
+            anyhow::bail!("Embedding with id '{}' is already indexed", id);
+        }
+        self.link(id.to_string(), normalize_l2_vec(embedding));
+        Ok(())
+    }
+
+    fn check_embedding(&self, id: &str, embedding: &[f32]) -> Result<()> {
+        if embedding.is_empty() {
+            anyhow::bail!("Embedding for '{}' must not be empty", id);
+        }
+        if let Some(first) = self.nodes.first() {
+            if first.vector.len() != embedding.len() {
+                anyhow::bail!(
+                    "Embedding for '{}' has {} dimensions, index expects {}",
+                    id,
+                    embedding.len(),
+                    first.vector.len()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Rebuild the graph from the live nodes, in their original insertion order
+    fn compact(&mut self) {
+        let live: Vec<Node> = std::mem::take(&mut self.nodes).into_iter().filter(|node| !node.deleted).collect();
+        self.positions.clear();
+        self.tombstones = 0;
+        self.entry_point = None;
+        self.max_level = 0;
+        for node in live {
+            self.link(node.id, node.vector);
+        }
+    }
+
+    // Add an already validated, normalized vector to the graph
+    fn link(&mut self, id: String, vector: Vec<f32>) {
+        let level = self.random_level();
+        let new = self.nodes.len();
+        self.positions.insert(id.clone(), new);
+        self.nodes.push(Node {
+            id,
+            vector,
+            neighbours: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+
+        let Some(mut entry) = self.entry_point else {
+            self.entry_point = Some(new);
+            self.max_level = level;
+            return;
+        };
+
+        let query = self.nodes[new].vector.clone();
+        for layer in (level + 1..=self.max_level).rev() {
+            entry = self.greedy_closest(&query, entry, layer);
+        }
+
+        let mut entries = vec![entry];
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(&query, &entries, self.ef_construction, layer);
+            let selected: Vec<usize> = candidates.iter().take(self.m).map(|s| s.1).collect();
+            for &neighbour in &selected {
+                self.nodes[neighbour].neighbours[layer].push(new);
+                self.prune(neighbour, layer);
+            }
+            self.nodes[new].neighbours[layer] = selected;
+            entries = candidates.into_iter().map(|s| s.1).collect();
+        }
+
+        if level > self.max_level {
+            self.entry_point = Some(new);
+            self.max_level = level;
+        }
+    }
+
+    // Return the (approximately) `k` most similar ids, most similar first
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<(String, f32)>> {
+        let Some(mut entry) = self.entry_point else {
+            return Ok(Vec::new());
+        };
+        let dims = self.nodes[entry].vector.len();
+        if query.len() != dims {
+            anyhow::bail!("Query has {} dimensions, index expects {}", query.len(), dims);
+        }
+
+        let query = normalize_l2_vec(query);
+        for layer in (1..=self.max_level).rev() {
+            entry = self.greedy_closest(&query, entry, layer);
+        }
+        // Widen the beam by the number of tombstones so removed nodes cannot crowd out live ones.
+        // Compaction keeps them a small share of the graph; the widening is capped at double anyway.
+        let ef = self.ef_search.max(k);
+        let results = self.search_layer(&query, &[entry], ef + self.tombstones.min(ef), 0);
+        Ok(results
+            .into_iter()
+            .filter(|Scored(_, node)| !self.nodes[*node].deleted)
+            .take(k)
+            .map(|Scored(similarity, node)| (self.nodes[node].id.clone(), similarity))
+            .collect())
+    }
+
+    fn random_level(&mut self) -> usize {
+        let uniform: f64 = self.rng.random_range(f64::MIN_POSITIVE..1.0);
+        (-uniform.ln() * self.level_multiplier).floor() as usize
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 { self.m * 2 } else { self.m }
+    }
+
+    fn similarity(&self, query: &[f32], node: usize) -> f32 {
+        dot(query, &self.nodes[node].vector)
+    }
+
+    // Walk towards the query on one layer until no neighbour is closer
+    fn greedy_closest(&self, query: &[f32], mut current: usize, layer: usize) -> usize {
+        let mut best = self.similarity(query, current);
+        loop {
+            let mut improved = false;
+            for &neighbour in &self.nodes[current].neighbours[layer] {
+                let similarity = self.similarity(query, neighbour);
+                if similarity > best {
+                    best = similarity;
+                    current = neighbour;
+                    improved = true;
+                }
+            }
+            if !improved {
+                return current;
+            }
+        }
+    }
+
+    // Beam search on one layer, returning up to `ef` nodes sorted by descending similarity
+    fn search_layer(&self, query: &[f32], entries: &[usize], ef: usize, layer: usize) -> Vec<Scored> {
+        let mut visited: HashSet<usize> = entries.iter().copied().collect();
+        let mut candidates: BinaryHeap<Scored> = BinaryHeap::new();
+        let mut results: BinaryHeap<Reverse<Scored>> = BinaryHeap::new();
+
+        for &entry in entries {
+            let scored = Scored(self.similarity(query, entry), entry);
+            candidates.push(scored);
+            results.push(Reverse(scored));
+            if results.len() > ef {
+                results.pop();
+            }
+        }
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = results.peek().map(|r| r.0 .0).unwrap_or(f32::NEG_INFINITY);
+            if candidate.0 < worst && results.len() >= ef {
+                break;
+            }
+            for &neighbour in &self.nodes[candidate.1].neighbours[layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let scored = Scored(self.similarity(query, neighbour), neighbour);
+                let worst = results.peek().map(|r| r.0 .0).unwrap_or(f32::NEG_INFINITY);
+                if results.len() < ef || scored.0 > worst {
+                    candidates.push(scored);
+                    results.push(Reverse(scored));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut results: Vec<Scored> = results.into_iter().map(|r| r.0).collect();
+        results.sort_by(|a, b| b.cmp(a));
+        results
+    }
+
+    // Keep only the most similar links once a node exceeds its link budget
+    fn prune(&mut self, node: usize, layer: usize) {
+        let max_links = self.max_links(layer);
+        if self.nodes[node].neighbours[layer].len() <= max_links {
+            return;
+        }
+        let base = self.nodes[node].vector.clone();
+        let mut scored: Vec<Scored> = self.nodes[node].neighbours[layer]
+            .iter()
+            .map(|&n| Scored(self.similarity(&base, n), n))
+            .collect();
+        scored.sort_by(|a, b| b.cmp(a));
+        scored.truncate(max_links);
+        self.nodes[node].neighbours[layer] = scored.into_iter().map(|s| s.1).collect();
+    }
+}
+
+// Storing over an indexed id replaces its vector; a soft-delete (an update that sets the
+// deleted flag) or an already-passed expiry drops it from the index, and a restore adds it back
+impl IndexObserver for HnswIndex {
+    fn on_insert(&mut self, record: &EmbeddingRecord) -> Result<()> {
+        if record.is_deleted() || record.is_expired_at(chrono::Utc::now()) {
+            self.remove(&record.id);
+            return Ok(());
+        }
+        // Checked first so a rejected vector leaves the old entry in place
+        self.check_embedding(&record.id, &record.embedding)?;
+        self.remove(&record.id);
+        self.insert(&record.id, &record.embedding)
+    }
+
+    fn on_delete(&mut self, id: &str) -> Result<()> {
+        self.remove(id);
+        Ok(())
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{add_record, top_k};
+    use ex04_storage_local_solution::{open_temp_storage, InMemoryStorage, ObservedStorage, TempFileGuard};
+    use std::sync::{Arc, Mutex};
+
+    fn random_vector(rng: &mut StdRng, dims: usize) -> Vec<f32> {
+        (0..dims).map(|_| rng.random_range(-1.0..1.0)).collect()
+    }
+
+    #[test]
+    fn hnsw_recall_matches_brute_force() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for i in 0..300 {
+            add_record(storage.as_mut(), &format!("person_{i}"), random_vector(&mut rng, 32))?;
+        }
+        let index = HnswIndex::from_storage(storage.as_ref())?;
+        assert_eq!(index.len(), 300);
+
+        let (mut hits, mut total) = (0, 0);
+        for _ in 0..20 {
+            let query = random_vector(&mut rng, 32);
+            let exact: HashSet<String> = top_k(storage.as_ref(), &query, 10)?
+                .into_iter()
+                .map(|(record, _)| record.id)
+                .collect();
+            let approx = index.search(&query, 10)?;
+            assert_eq!(approx.len(), 10);
+            hits += approx.iter().filter(|(id, _)| exact.contains(id)).count();
+            total += exact.len();
+        }
+        let recall = hits as f32 / total as f32;
+        assert!(recall > 0.95, "recall@10 was {}", recall);
+        Ok(())
+    }
+
+    #[test]
+    fn hnsw_insert_is_incremental_and_validated() -> Result<()> {
+        let mut index = HnswIndex::new();
+        assert!(index.search(&[1.0, 0.0], 5)?.is_empty());
+
+        index.insert("a", &[1.0, 0.0])?;
+        index.insert("b", &[0.0, 1.0])?;
+        assert_eq!(index.search(&[0.9, 0.1], 1)?[0].0, "a");
+        index.insert("c", &[1.0, 0.05])?;
+        assert_eq!(index.search(&[1.0, 0.05], 1)?[0].0, "c");
+
+        assert!(index.insert("a", &[0.5, 0.5]).is_err(), "Duplicate ids should be rejected");
+        assert!(index.insert("d", &[1.0, 0.0, 0.0]).is_err(), "Dimension mismatch should be rejected");
+        assert!(index.search(&[1.0], 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn same_seed_builds_identical_index() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(3);
+        let vectors: Vec<Vec<f32>> = (0..200).map(|_| random_vector(&mut rng, 16)).collect();
+        let build = |seed: u64| -> Result<HnswIndex> {
+            let mut index = HnswIndex::with_seed(seed);
+            for (i, v) in vectors.iter().enumerate() {
+                index.insert(&format!("person_{i}"), v)?;
+            }
+            Ok(index)
+        };
+
+        let (a, b) = (build(42)?, build(42)?);
+        for _ in 0..10 {
+            let query = random_vector(&mut rng, 16);
+            assert_eq!(a.search(&query, 10)?, b.search(&query, 10)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn observed_storage_keeps_index_in_sync() -> Result<()> {
+        let index = Arc::new(Mutex::new(HnswIndex::new()));
+        let mut storage = ObservedStorage::new(InMemoryStorage::new());
+        storage.add_observer(Box::new(Arc::clone(&index)));
+
+        let alice = add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+        let bob = add_record(&mut storage, "bob", vec![0.0, 1.0])?;
+        assert_eq!(index.lock().unwrap().len(), 2);
+        assert_eq!(index.lock().unwrap().search(&[1.0, 0.1], 1)?[0].0, alice);
+
+        assert!(storage.soft_delete(&bob)?);
+        assert_eq!(index.lock().unwrap().len(), 1);
+        assert!(storage.restore(&bob)?);
+        assert_eq!(index.lock().unwrap().len(), 2);
+
+        // An update that moves the expiry into the past drops the record like `from_storage` does
+        let mut expired = storage.get_embedding(&bob)?.expect("bob was just restored");
+        expired.expires_at = Some(chrono::Utc::now() - chrono::Duration::minutes(1));
+        storage.update_embedding(expired)?;
+        assert_eq!(index.lock().unwrap().len(), 1);
+        let mut renewed = storage.get_embedding(&bob)?.expect("bob is still stored");
+        renewed.expires_at = None;
+        storage.update_embedding(renewed)?;
+        assert_eq!(index.lock().unwrap().len(), 2);
+
+        assert!(storage.delete_embedding(&alice)?);
+        let index = index.lock().unwrap();
+        assert_eq!(index.len(), 1);
+        let results = index.search(&[1.0, 0.1], 5)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, bob, "A deleted record is no longer returned");
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_updates_do_not_accumulate_tombstones() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(9);
+        let index = Arc::new(Mutex::new(HnswIndex::new()));
+        let mut storage = ObservedStorage::new(InMemoryStorage::new());
+        storage.add_observer(Box::new(Arc::clone(&index)));
+        let ids: Vec<String> = (0..20)
+            .map(|i| add_record(&mut storage, &format!("person_{i}"), random_vector(&mut rng, 8)))
+            .collect::<Result<_>>()?;
+
+        for round in 0..50 {
+            let mut record = storage.get_embedding(&ids[round % ids.len()])?.expect("record is stored");
+            record.embedding = random_vector(&mut rng, 8);
+            storage.update_embedding(record)?;
+        }
+        let index = index.lock().unwrap();
+        assert_eq!(index.len(), 20);
+        assert!(index.nodes.len() <= 20 + 20 / (TOMBSTONE_DIVISOR - 1) + 1, "{} nodes for 20 records", index.nodes.len());
+        assert_eq!(index.search(&random_vector(&mut rng, 8), 20)?.len(), 20);
+        Ok(())
+    }
+
+    #[test]
+    fn rejected_update_keeps_the_old_entry() -> Result<()> {
+        let mut index = HnswIndex::new();
+        index.insert("a", &[1.0, 0.0])?;
+        index.insert("b", &[0.0, 1.0])?;
+        let mut record = crate::new_record("a".to_string(), vec![1.0, 0.0, 0.0], chrono::Utc::now());
+        record.id = "a".to_string();
+        assert!(index.on_insert(&record).is_err());
+        assert_eq!(index.search(&[1.0, 0.0], 1)?[0].0, "a");
+        record.embedding = vec![0.6, 0.8];
+        index.on_insert(&record)?;
+        assert!((index.search(&[0.6, 0.8], 1)?[0].1 - 1.0).abs() < 1e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn from_storage_skips_soft_deleted_and_expired_records() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let alice = add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+        let bob = add_record(&mut storage, "bob", vec![0.9, 0.1])?;
+        let carol = add_record(&mut storage, "carol", vec![0.8, 0.2])?;
+        storage.soft_delete(&bob)?;
+        let mut expired = storage.get_embedding(&carol)?.expect("carol was just stored");
+        expired.expires_at = Some(chrono::Utc::now() - chrono::Duration::minutes(1));
+        storage.update_embedding(expired)?;
+
+        let index = HnswIndex::from_storage(&storage)?;
+        assert_eq!(index.len(), 1);
+        let results = index.search(&[1.0, 0.0], 3)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, alice);
+        Ok(())
+    }
+
+    #[test]
+    fn removed_ids_can_be_reinserted() -> Result<()> {
+        let mut index = HnswIndex::new();
+        index.insert("a", &[1.0, 0.0])?;
+        index.insert("b", &[0.0, 1.0])?;
+        assert!(index.remove("a"));
+        assert!(!index.remove("a"));
+        assert_eq!(index.search(&[1.0, 0.0], 2)?.len(), 1);
+
+        index.insert("a", &[0.0, 1.0])?;
+        assert_eq!(index.len(), 2);
+        assert!((index.search(&[0.0, 1.0], 2)?[1].1 - 1.0).abs() < 1e-6);
+        Ok(())
+    }
+}
@@ -1,41 +1,1472 @@
 use anyhow::Result;
-use candle_core::{Tensor, Device};
-use ex03_similarity_solution::cosine_similarity;
-use ex04_storage_local_solution::{EmbeddingRecord, EmbeddingStorage};
-use std::collections::HashMap;
+use candle_core::Tensor;
+use chrono::{DateTime, Utc};
+use ex01_image_processing_solution::liveness::Liveness;
+use ex03_similarity_solution::audit::{AuditLog, AuthAttempt};
+use ex03_similarity_solution::ratelimit::RateLimiter;
+use ex03_similarity_solution::verification::is_match;
+use ex03_similarity_solution::{cosine_similarity_vec, dot_similarity_vec, normalize_l2_vec, score, Metric};
+use ex04_storage_local_solution::{AsyncEmbeddingStorage, EmbeddingRecord, EmbeddingStorage};
+use face_auth_error::FaceAuthError;
+use rayon::prelude::*;
+use std::borrow::Borrow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use uuid::Uuid;
 
+pub mod dedup;
+mod hnsw;
+pub mod lsh;
+pub mod stats;
+pub use hnsw::HnswIndex;
+
+// Metadata key holding the L2 norm of the embedding as it was enrolled, before any normalization
+pub const L2_NORM_KEY: &str = "l2_norm";
+
+// Metadata flag set to "true" on records whose embedding was stored L2-normalized. Cosine search
+// scores those with a plain dot product against the normalized query.
+pub const NORMALIZED_KEY: &str = "normalized";
 
 pub fn add_record(storage: &mut dyn EmbeddingStorage, name: &str, embedding: Vec<f32>) -> Result<String> {
+    let record = new_record(name.to_string(), embedding, chrono::Utc::now());
+    
+    let id = record.id.clone();
+    storage.store_embedding(record)?;
+    Ok(id)
+}
+
+// `add_record`, storing the embedding L2-normalized and flagged with `NORMALIZED_KEY` so cosine
+// searches can use the dot-product fast path. `l2_norm` still records the raw norm.
+pub fn add_record_normalized(storage: &mut dyn EmbeddingStorage, name: &str, embedding: Vec<f32>) -> Result<String> {
+    let mut record = new_record(name.to_string(), normalize_l2_vec(&embedding), chrono::Utc::now());
+    record.metadata = norm_metadata(l2_norm(&embedding));
+    record.metadata.insert(NORMALIZED_KEY.to_string(), "true".to_string());
+
+    let id = record.id.clone();
+    storage.store_embedding(record)?;
+    Ok(id)
+}
+
+// Whether `record` was stored L2-normalized, see `NORMALIZED_KEY`
+pub fn is_normalized(record: &EmbeddingRecord) -> bool {
+    record.metadata.get(NORMALIZED_KEY).is_some_and(|flag| flag == "true")
+}
+
+// Insert many (name, embedding) entries in one storage call; returns the new ids in input order
+pub fn add_records(storage: &mut dyn EmbeddingStorage, entries: Vec<(String, Vec<f32>)>) -> Result<Vec<String>> {
+    let created_at = chrono::Utc::now();
+    let records: Vec<EmbeddingRecord> = entries
+        .into_iter()
+        .map(|(name, embedding)| new_record(name, embedding, created_at))
+        .collect();
+
+    let ids = records.iter().map(|record| record.id.clone()).collect();
+    storage.store_embeddings(records)?;
+    Ok(ids)
+}
+
+// Like `add_records`, but records are stored one at a time and `on_progress(done, total)` is
+// called after each, so exactly `total` times with `done` counting up from 1. If a store fails
+// the records before it stay enrolled.
+pub fn add_records_with_progress(
+    storage: &mut dyn EmbeddingStorage,
+    entries: Vec<(String, Vec<f32>)>,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<Vec<String>> {
+    let created_at = chrono::Utc::now();
+    let total = entries.len();
+    let mut ids = Vec::with_capacity(total);
+    for (done, (name, embedding)) in entries.into_iter().enumerate() {
+        let record = new_record(name, embedding, created_at);
+        ids.push(record.id.clone());
+        storage.store_embedding(record)?;
+        on_progress(done + 1, total);
+    }
+    Ok(ids)
+}
+
+// A fresh record with a new id and the enrollment-time metadata
+fn new_record(name: String, embedding: Vec<f32>, created_at: DateTime<Utc>) -> EmbeddingRecord {
+    EmbeddingRecord {
+        id: Uuid::new_v4().to_string(),
+        name,
+        metadata: norm_metadata(l2_norm(&embedding)),
+        embedding,
+        created_at,
+        expires_at: None,
+    }
+}
+
+// Enroll one person from several shots: each embedding is L2-normalized, the mean is
+// re-normalized and stored as a single template. `source_images` records how many were averaged,
+// `l2_norm` the mean norm of the raw shots (the template itself always has norm 1, so it is
+// flagged with `NORMALIZED_KEY`).
+pub fn enroll_identity(storage: &mut dyn EmbeddingStorage, name: &str, embeddings: &[Vec<f32>]) -> Result<String> {
+    if embeddings.is_empty() {
+        anyhow::bail!("Cannot enroll '{}' without any embeddings", name);
+    }
+
+    let mean_norm = embeddings.iter().map(|e| l2_norm(e)).sum::<f32>() / embeddings.len() as f32;
+    let mut metadata = norm_metadata(mean_norm);
+    metadata.insert("source_images".to_string(), embeddings.len().to_string());
+    metadata.insert(NORMALIZED_KEY.to_string(), "true".to_string());
     let record = EmbeddingRecord {
         id: Uuid::new_v4().to_string(),
         name: name.to_string(),
-        embedding,
+        embedding: average_normalized(embeddings)?,
         created_at: chrono::Utc::now(),
-        metadata: HashMap::new(),
+        metadata,
+        expires_at: None,
     };
-    
+
     let id = record.id.clone();
     storage.store_embedding(record)?;
     Ok(id)
 }
 
-// Get top-k most similar embeddings  
-// Get top-k most similar embeddings to the query
-pub fn top_k(storage: &dyn EmbeddingStorage, query: &[f32], k: usize) -> Result<Vec<(EmbeddingRecord, f32)>> {
+fn l2_norm(embedding: &[f32]) -> f32 {
+    embedding.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+fn norm_metadata(norm: f32) -> HashMap<String, String> {
+    HashMap::from([(L2_NORM_KEY.to_string(), norm.to_string())])
+}
+
+// L2-normalize each embedding, average them and re-normalize the mean
+pub(crate) fn average_normalized(embeddings: &[Vec<f32>]) -> Result<Vec<f32>> {
+    let Some(first) = embeddings.first() else {
+        anyhow::bail!("Cannot average an empty set of embeddings");
+    };
+    let dims = first.len();
+    let mut sum = vec![0f32; dims];
+    for embedding in embeddings {
+        if embedding.len() != dims {
+            anyhow::bail!("Embedding lengths do not match: {} vs {}", embedding.len(), dims);
+        }
+        for (total, value) in sum.iter_mut().zip(normalize_l2_vec(embedding)) {
+            *total += value;
+        }
+    }
+    Ok(normalize_l2_vec(&sum))
+}
+
+// Score every stored embedding against the query and return the `limit` most similar.
+// Equal scores are ordered by `created_at` (oldest first), then by id, so the same gallery
+// always gives the same order whatever order the storage yields records in.
+pub fn search_similar(storage: &dyn EmbeddingStorage, embedding: &[f32], limit: usize) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    search_similar_with_metric(storage, embedding, limit, None)
+}
+
+// Like `search_similar`, ranked with `metric` (cosine when `None`). Returned scores are the raw
+// metric values, so for `Metric::Euclidean` they are distances in ascending order.
+// Cosine searches go to `EmbeddingStorage::nearest` first, for backends that rank in the database.
+pub fn search_similar_with_metric(
+    storage: &dyn EmbeddingStorage,
+    embedding: &[f32],
+    limit: usize,
+    metric: Option<Metric>,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    let metric = metric.unwrap_or_default();
+    if metric == Metric::Cosine && limit > 0 {
+        if let Some(results) = storage.nearest(embedding, limit)? {
+            return Ok(results);
+        }
+    }
+    rank_records(storage.iter_embeddings(), embedding, limit, metric)
+}
+
+// Async counterpart of `search_similar`. Scoring is CPU-bound, so like the storage I/O it runs
+// on tokio's blocking thread pool
+pub async fn search_similar_async<S: AsyncEmbeddingStorage>(
+    storage: &S,
+    embedding: &[f32],
+    limit: usize,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    let records = storage.get_all_embeddings().await?;
+    let embedding = embedding.to_vec();
+    tokio::task::spawn_blocking(move || rank_records(records.into_iter().map(Ok), &embedding, limit, Metric::Cosine))
+        .await?
+}
+
+// Score several queries against the gallery, which is fetched from storage only once.
+// The i-th result list belongs to the i-th query.
+pub fn search_similar_batch(
+    storage: &dyn EmbeddingStorage,
+    queries: &[Vec<f32>],
+    limit: usize,
+) -> Result<Vec<Vec<(EmbeddingRecord, f32)>>> {
     let records = storage.get_all_embeddings()?;
-    let mut results = Vec::new();
-    let embedding_tensor = Tensor::from_slice(query, (1, query.len()), &Device::Cpu)?;
-    
-    for record in records {
-        let record_embedding_tensor = Tensor::from_slice(&record.embedding, (1, record.embedding.len()), &Device::Cpu)?;
-        let similarity = cosine_similarity(&embedding_tensor, &record_embedding_tensor)?;
-        results.push((record, similarity));
+    queries
+        .iter()
+        .map(|query| rank_records(records.iter().map(Ok), query, limit, Metric::Cosine))
+        .collect()
+}
+
+// Like `search_similar`, but only records whose metadata contains every key/value pair in `filter` are scored
+pub fn search_similar_filtered(
+    storage: &dyn EmbeddingStorage,
+    embedding: &[f32],
+    limit: usize,
+    filter: &HashMap<String, String>,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    let records = storage.iter_embeddings().filter(|record| match record {
+        Ok(record) => filter.iter().all(|(key, value)| record.metadata.get(key) == Some(value)),
+        Err(_) => true,
+    });
+    rank_records(records, embedding, limit, Metric::Cosine)
+}
+
+// Lazily yield every record scoring at least `min_similarity`, in storage order and without any
+// sorting, as the gallery is scanned. Storage and dimension errors come through as `Err` items.
+pub fn search_stream<'a>(
+    storage: &'a dyn EmbeddingStorage,
+    query: &'a [f32],
+    min_similarity: f32,
+) -> impl Iterator<Item = Result<(EmbeddingRecord, f32)>> + 'a {
+    let now = Utc::now();
+    storage.iter_embeddings().filter_map(move |record| {
+        let record = match record {
+            Ok(record) if record.is_expired_at(now) || record.is_deleted() => return None,
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+        match cosine_similarity_vec(query, &record.embedding) {
+            Ok(similarity) if is_match(similarity, min_similarity) => Some(Ok((record, similarity))),
+            Ok(_) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    })
+}
+
+// Like `search_similar`, scoped to one collection: records of other collections are never scored
+pub fn search_similar_in(
+    storage: &dyn EmbeddingStorage,
+    collection: &str,
+    embedding: &[f32],
+    limit: usize,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    rank_records(storage.iter_embeddings_in(collection), embedding, limit, Metric::Cosine)
+}
+
+// Like `search_similar`, but only records enrolled at or after `since` are scored
+pub fn search_similar_since(
+    storage: &dyn EmbeddingStorage,
+    embedding: &[f32],
+    limit: usize,
+    since: DateTime<Utc>,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    search_similar_between(storage, embedding, limit, Some(since), None)
+}
+
+// Like `search_similar`, but only records enrolled at or before `until` are scored
+pub fn search_similar_until(
+    storage: &dyn EmbeddingStorage,
+    embedding: &[f32],
+    limit: usize,
+    until: DateTime<Utc>,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    search_similar_between(storage, embedding, limit, None, Some(until))
+}
+
+// Restrict the search to records whose `created_at` lies inside both bounds (inclusive);
+// a `None` bound leaves that side of the window open
+pub fn search_similar_between(
+    storage: &dyn EmbeddingStorage,
+    embedding: &[f32],
+    limit: usize,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    let records = storage.iter_embeddings().filter(|record| match record {
+        Ok(record) => {
+            since.is_none_or(|since| record.created_at >= since) && until.is_none_or(|until| record.created_at <= until)
+        }
+        Err(_) => true,
+    });
+    rank_records(records, embedding, limit, Metric::Cosine)
+}
+
+// Records are pulled from the stream and scored in parallel one chunk at a time
+const SCORING_CHUNK: usize = 1024;
+
+// A scored record, ordered so that the better candidate compares greater: higher `key` first,
+// ties broken by earlier `created_at`, then smaller id. `key` is the score oriented so that larger is better.
+struct Candidate<R> {
+    key: f32,
+    score: f32,
+    record: R,
+}
+
+impl<R: Borrow<EmbeddingRecord>> PartialEq for Candidate<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
-    
-    // Sort by similarity (descending) and take top results
-    results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-    results.truncate(k);
-    
+}
+
+impl<R: Borrow<EmbeddingRecord>> Eq for Candidate<R> {}
+
+impl<R: Borrow<EmbeddingRecord>> PartialOrd for Candidate<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<R: Borrow<EmbeddingRecord>> Ord for Candidate<R> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b) = (self.record.borrow(), other.record.borrow());
+        self.key
+            .total_cmp(&other.key)
+            .then_with(|| b.created_at.cmp(&a.created_at))
+            .then_with(|| b.id.cmp(&a.id))
+    }
+}
+
+// Keep the `limit` best records in a bounded min-heap, so memory stays O(limit + chunk)
+// no matter how large the gallery is
+fn rank_records<R>(
+    mut records: impl Iterator<Item = Result<R>>,
+    embedding: &[f32],
+    limit: usize,
+    metric: Metric,
+) -> Result<Vec<(EmbeddingRecord, f32)>>
+where
+    R: Borrow<EmbeddingRecord> + Send + Sync,
+{
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    // Cosine against a record stored unit-length is a dot product with the query normalized once
+    let unit_query = (metric == Metric::Cosine).then(|| normalize_l2_vec(embedding));
+    let mut heap: BinaryHeap<Reverse<Candidate<R>>> = BinaryHeap::with_capacity(limit.min(SCORING_CHUNK) + 1);
+    let mut chunk: Vec<R> = Vec::with_capacity(SCORING_CHUNK);
+    let mut position = 0;
+    let now = Utc::now();
+    loop {
+        // Expired and soft-deleted records are skipped here, so searches ignore them even before a purge
+        let mut pulled = 0;
+        for record in records.by_ref().take(SCORING_CHUNK) {
+            pulled += 1;
+            let record = record?;
+            if !record.borrow().is_expired_at(now) && !record.borrow().is_deleted() {
+                chunk.push(record);
+            }
+        }
+        if pulled == 0 {
+            break;
+        }
+        // A chunk may hold nothing but skipped records; live ones can still follow it
+        let Some(first) = chunk.first() else {
+            continue;
+        };
+        // Storage keeps every record at one length, so the first is representative
+        let dims = first.borrow().embedding.len();
+        if position == 0 && dims != embedding.len() {
+            return Err(FaceAuthError::DimensionMismatch {
+                expected: dims,
+                actual: embedding.len(),
+            }
+            .into());
+        }
+
+        let scores = chunk
+            .par_iter()
+            .map(|record| {
+                let record = record.borrow();
+                Ok(match &unit_query {
+                    Some(query) if is_normalized(record) => dot_similarity_vec(query, &record.embedding)?,
+                    _ => score(metric, embedding, &record.embedding)?,
+                })
+            })
+            .collect::<Result<Vec<f32>>>()?;
+        for (record, score) in chunk.drain(..).zip(scores) {
+            let key = if metric.higher_is_better() { score } else { -score };
+            let candidate = Candidate { key, score, record };
+            position += 1;
+            if heap.len() < limit {
+                heap.push(Reverse(candidate));
+            } else if heap.peek().is_some_and(|worst| candidate > worst.0) {
+                heap.pop();
+                heap.push(Reverse(candidate));
+            }
+        }
+    }
+
+    let mut best: Vec<Candidate<R>> = heap.into_iter().map(|candidate| candidate.0).collect();
+    best.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(best
+        .into_iter()
+        .map(|candidate| (candidate.record.borrow().clone(), candidate.score))
+        .collect())
+}
+
+// Get top-k most similar embeddings to the query
+pub fn top_k(storage: &dyn EmbeddingStorage, query: &[f32], k: usize) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    top_k_threshold(storage, query, k, f32::NEG_INFINITY)
+}
+
+// Async counterpart of `top_k`
+pub async fn top_k_async<S: AsyncEmbeddingStorage>(
+    storage: &S,
+    query: &[f32],
+    k: usize,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    search_similar_async(storage, query, k).await
+}
+
+// `top_k` with a selectable metric, see `search_similar_with_metric`
+pub fn top_k_with_metric(
+    storage: &dyn EmbeddingStorage,
+    query: &[f32],
+    k: usize,
+    metric: Option<Metric>,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    search_similar_with_metric(storage, query, k, metric)
+}
+
+// Like `top_k`, but candidates scoring below `min_similarity` are dropped, so fewer than k (or none) may come back
+pub fn top_k_threshold(
+    storage: &dyn EmbeddingStorage,
+    query: &[f32],
+    k: usize,
+    min_similarity: f32,
+) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    let mut results = search_similar(storage, query, k)?;
+    results.retain(|(_, similarity)| is_match(*similarity, min_similarity));
     Ok(results)
-}
\ No newline at end of file
+}
+
+// Like `top_k`, but only the best-scoring record per `name` is kept, so a person enrolled
+// with several photos appears once. Records with an empty name are each treated as distinct.
+pub fn top_k_by_name(storage: &dyn EmbeddingStorage, query: &[f32], k: usize) -> Result<Vec<(EmbeddingRecord, f32)>> {
+    let ranked = search_similar(storage, query, usize::MAX)?;
+    let mut seen = HashSet::new();
+    Ok(ranked
+        .into_iter()
+        .filter(|(record, _)| record.name.is_empty() || seen.insert(record.name.clone()))
+        .take(k)
+        .collect())
+}
+
+// k-NN vote: the `k` nearest neighbours vote for their `name` with their similarity as weight.
+// The winning name is returned only if its summed similarity reaches `threshold`; a tie goes to
+// the name holding the single most similar neighbour, then to the name that sorts first. Unnamed records do not vote.
+pub fn classify_knn(storage: &dyn EmbeddingStorage, query: &[f32], k: usize, threshold: f32) -> Result<Option<String>> {
+    // name -> (summed similarity, best single similarity); neighbours arrive most similar first
+    let mut tally: HashMap<String, (f32, f32)> = HashMap::new();
+    for (record, similarity) in top_k(storage, query, k)? {
+        if record.name.is_empty() {
+            continue;
+        }
+        let entry = tally.entry(record.name).or_insert((0.0, similarity));
+        entry.0 += similarity;
+    }
+
+    let winner = tally.into_iter().max_by(|(name_a, a), (name_b, b)| {
+        a.0.total_cmp(&b.0)
+            .then_with(|| a.1.total_cmp(&b.1))
+            .then_with(|| name_b.cmp(name_a))
+    });
+    Ok(winner
+        .filter(|(_, (score, _))| is_match(*score, threshold))
+        .map(|(name, _)| name))
+}
+
+// 1:N identification: the single best match if it reaches `threshold`, `None` for an unknown face
+pub fn identify(
+    storage: &dyn EmbeddingStorage,
+    query: &[f32],
+    threshold: f32,
+) -> Result<Option<(EmbeddingRecord, f32)>> {
+    identify_audited(storage, query, threshold, None)
+}
+
+// `identify`, logging the best candidate and the outcome to `log` when one is given
+pub fn identify_audited(
+    storage: &dyn EmbeddingStorage,
+    query: &[f32],
+    threshold: f32,
+    log: Option<&dyn AuditLog>,
+) -> Result<Option<(EmbeddingRecord, f32)>> {
+    let best = top_k(storage, query, 1)?.into_iter().next();
+    let passed = best.as_ref().is_some_and(|(_, similarity)| is_match(*similarity, threshold));
+    if let Some(log) = log {
+        log.record_attempt(AuthAttempt {
+            at: Utc::now(),
+            candidate_id: best.as_ref().map(|(record, _)| record.id.clone()),
+            candidate_name: best.as_ref().map(|(record, _)| record.name.clone()),
+            similarity: best.as_ref().map(|(_, similarity)| *similarity),
+            threshold,
+            passed,
+        })?;
+    }
+    Ok(best.filter(|_| passed))
+}
+
+// `identify_audited` behind `limiter`: over the limit for `key` it fails with
+// `FaceAuthError::RateLimited` without searching the gallery
+pub fn identify_rate_limited(
+    storage: &dyn EmbeddingStorage,
+    query: &[f32],
+    threshold: f32,
+    limiter: &RateLimiter,
+    key: &str,
+    log: Option<&dyn AuditLog>,
+) -> Result<Option<(EmbeddingRecord, f32)>> {
+    limiter.acquire(key)?;
+    identify_audited(storage, query, threshold, log)
+}
+
+// `identify_audited` for a probe that must first pass `liveness`: when `image` scores below
+// `min_liveness` the gallery is not searched and the attempt is rejected (and logged without a candidate)
+pub fn identify_live(
+    storage: &dyn EmbeddingStorage,
+    query: &[f32],
+    image: &Tensor,
+    threshold: f32,
+    liveness: &dyn Liveness,
+    min_liveness: f32,
+    log: Option<&dyn AuditLog>,
+) -> Result<Option<(EmbeddingRecord, f32)>> {
+    if liveness.check(image)? < min_liveness {
+        if let Some(log) = log {
+            log.record_attempt(AuthAttempt {
+                at: Utc::now(),
+                candidate_id: None,
+                candidate_name: None,
+                similarity: None,
+                threshold,
+                passed: false,
+            })?;
+        }
+        return Ok(None);
+    }
+    identify_audited(storage, query, threshold, log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ex01_image_processing_solution::imagenet::load_image224;
+    use ex02_embeddings_solution::{build_model, compute_embedding};
+    use ex03_similarity_solution::audit::JsonlAuditLog;
+    use ex03_similarity_solution::verification::DEFAULT_MATCH_THRESHOLD;
+    use ex04_storage_local_solution::{
+        BlockingAdapter, InMemoryStorage, LocalFileStorage, SharedStorage, SqliteStorage, DEFAULT_COLLECTION,
+        open_temp_storage, TempFileGuard,
+    };
+    use std::cell::Cell;
+
+    fn fixture_embedding(model: &candle_nn::Func, path: &str) -> Result<Vec<f32>> {
+        let image = load_image224(path)?;
+        Ok(compute_embedding(model, &image)?.squeeze(0)?.to_vec1::<f32>()?)
+    }
+
+    #[test]
+    fn deleted_record_is_not_returned_by_search() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        let query = vec![1.0, 0.0, 0.0];
+        let alice = add_record(storage.as_mut(), "alice", vec![1.0, 0.0, 0.0])?;
+        add_record(storage.as_mut(), "bob", vec![0.0, 1.0, 0.0])?;
+        assert_eq!(search_similar(storage.as_ref(), &query, 1)?[0].0.name, "alice");
+
+        assert!(storage.delete_embedding(&alice)?);
+        assert!(!storage.delete_embedding(&alice)?, "Deleting a missing id should return false");
+
+        let results = search_similar(storage.as_ref(), &query, 10)?;
+        assert_eq!(results.len(), 1);
+        assert!(results.iter().all(|(record, _)| record.id != alice));
+        Ok(())
+    }
+
+    #[test]
+    fn updated_record_replaces_old_embedding() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        let id = add_record(storage.as_mut(), "alice", vec![0.0, 1.0])?;
+        let mut record = storage.get_embedding(&id)?.expect("alice should exist");
+        record.embedding = vec![1.0, 0.0];
+        storage.update_embedding(record)?;
+
+        let results = search_similar(storage.as_ref(), &[1.0, 0.0], 1)?;
+        assert_eq!(results[0].0.id, id);
+        assert!((results[0].1 - 1.0).abs() < 1e-6);
+        assert_eq!(storage.get_all_embeddings()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn added_record_can_be_fetched_by_id() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        let id = add_record(storage.as_mut(), "alice", vec![0.25, 0.5, 0.75])?;
+        add_record(storage.as_mut(), "bob", vec![1.0, 0.0, 0.0])?;
+
+        let fetched = storage.get_embedding(&id)?.expect("alice should exist");
+        assert_eq!(fetched.name, "alice");
+        assert_eq!(fetched.embedding, vec![0.25, 0.5, 0.75]);
+        assert!(storage.get_embedding("missing-id")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn metadata_filter_excludes_non_matching_records() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        for (name, department, embedding) in [
+            ("closest_sales", "sales", vec![1.0, 0.0, 0.0]),
+            ("eng_a", "eng", vec![0.7, 0.7, 0.0]),
+            ("eng_b", "eng", vec![0.0, 1.0, 0.0]),
+        ] {
+            let mut metadata = HashMap::new();
+            metadata.insert("department".to_string(), department.to_string());
+            storage.store_embedding(EmbeddingRecord {
+                id: Uuid::new_v4().to_string(),
+                name: name.to_string(),
+                embedding,
+                created_at: chrono::Utc::now(),
+                metadata,
+                expires_at: None,
+            })?;
+        }
+
+        let query = vec![1.0, 0.0, 0.0];
+        let mut filter = HashMap::new();
+        filter.insert("department".to_string(), "eng".to_string());
+        let filtered = search_similar_filtered(storage.as_ref(), &query, 10, &filter)?;
+        let names: Vec<_> = filtered.iter().map(|(r, _)| r.name.as_str()).collect();
+        assert_eq!(names, vec!["eng_a", "eng_b"]);
+
+        let unfiltered = search_similar(storage.as_ref(), &query, 10)?;
+        let empty_filter = search_similar_filtered(storage.as_ref(), &query, 10, &HashMap::new())?;
+        assert_eq!(unfiltered, empty_filter);
+        assert_eq!(unfiltered[0].0.name, "closest_sales");
+        Ok(())
+    }
+
+    // The documented search order: similarity descending, then oldest `created_at`, then smallest id
+    fn by_rank(a: &(EmbeddingRecord, f32), b: &(EmbeddingRecord, f32)) -> Ordering {
+        b.1.total_cmp(&a.1)
+            .then_with(|| a.0.created_at.cmp(&b.0.created_at))
+            .then_with(|| a.0.id.cmp(&b.0.id))
+    }
+
+    #[test]
+    fn equal_scores_are_ordered_by_created_at_then_id() -> Result<()> {
+        let created_at = Utc::now();
+        let tied = |id: &str, age_secs: i64| EmbeddingRecord {
+            id: id.to_string(),
+            name: id.to_string(),
+            embedding: vec![1.0, 0.0],
+            created_at: created_at - chrono::Duration::seconds(age_secs),
+            metadata: HashMap::new(),
+            expires_at: None,
+        };
+        // "b" and "c" were enrolled together, "d" earlier; all score exactly 1.0
+        let records = [tied("c", 0), tied("b", 0), tied("d", 60), tied("a", 0)];
+        let expected = ["d", "a", "b", "c"];
+
+        for rotation in 0..records.len() {
+            let mut storage = InMemoryStorage::new();
+            let mut batch = records.to_vec();
+            batch.rotate_left(rotation);
+            storage.store_embeddings(batch)?;
+            let ids: Vec<String> = search_similar(&storage, &[2.0, 0.0], 4)?.into_iter().map(|(r, _)| r.id).collect();
+            assert_eq!(ids, expected);
+            assert_eq!(search_similar(&storage, &[2.0, 0.0], 2)?[1].0.id, "a");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parallel_search_matches_sequential_baseline() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        // Several exact duplicates so the ordering of ties is exercised
+        for i in 0..200 {
+            let angle = (i % 25) as f32 * 0.1;
+            add_record(storage.as_mut(), &format!("person_{i}"), vec![angle.cos(), angle.sin(), 0.5])?;
+        }
+        let query = vec![1.0, 0.2, 0.5];
+
+        let mut baseline: Vec<(EmbeddingRecord, f32)> = storage
+            .get_all_embeddings()?
+            .into_iter()
+            .map(|record| {
+                let similarity = cosine_similarity_vec(&query, &record.embedding)?;
+                Ok((record, similarity))
+            })
+            .collect::<Result<_>>()?;
+        baseline.sort_by(by_rank);
+
+        for limit in [1, 7, 40, 200, 500] {
+            let parallel = search_similar(storage.as_ref(), &query, limit)?;
+            let expected: Vec<_> = baseline.iter().take(limit).cloned().collect();
+            assert_eq!(parallel, expected, "Mismatch for limit {}", limit);
+        }
+        assert!(top_k(storage.as_ref(), &query, 0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_face_is_cut_off_by_threshold() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        let model = build_model()?;
+
+        add_record(storage.as_mut(), "brad", fixture_embedding(&model, "../../../app/test_images/brad1.png")?)?;
+        let tom = fixture_embedding(&model, "../../../app/test_images/tom.png")?;
+        assert_eq!(top_k(storage.as_ref(), &tom, 1)?.len(), 1);
+        assert!(top_k_threshold(storage.as_ref(), &tom, 1, DEFAULT_MATCH_THRESHOLD)?.is_empty());
+
+        let brad = fixture_embedding(&model, "../../../app/test_images/brad2.png")?;
+        let results = top_k_threshold(storage.as_ref(), &brad, 1, DEFAULT_MATCH_THRESHOLD)?;
+        assert_eq!(results[0].0.name, "brad");
+        Ok(())
+    }
+
+    #[test]
+    fn threshold_drops_low_scoring_candidates() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        add_record(storage.as_mut(), "close", vec![1.0, 0.1])?;
+        add_record(storage.as_mut(), "far", vec![0.0, 1.0])?;
+        let results = top_k_threshold(storage.as_ref(), &[1.0, 0.0], 5, 0.5)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0.name, "close");
+        assert!(top_k_threshold(storage.as_ref(), &[-1.0, 0.0], 5, 0.5)?.is_empty());
+        assert_eq!(top_k(storage.as_ref(), &[-1.0, 0.0], 5)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn averaged_template_matches_each_source_photo() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        let model = build_model()?;
+
+        let shots = [
+            "../../../app/test_images/brad1.png",
+            "../../../app/test_images/brad2.png",
+            "../../../app/test_images/brad1_rotated_exif.jpg",
+        ]
+        .iter()
+        .map(|path| fixture_embedding(&model, path))
+        .collect::<Result<Vec<_>>>()?;
+        let id = enroll_identity(storage.as_mut(), "brad", &shots)?;
+
+        let template = storage.get_embedding(&id)?.expect("template should be stored");
+        assert_eq!(template.metadata.get("source_images").map(String::as_str), Some("3"));
+        for shot in &shots {
+            assert!(cosine_similarity_vec(&template.embedding, shot)? >= DEFAULT_MATCH_THRESHOLD);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn enroll_identity_normalizes_and_validates() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        assert!(enroll_identity(storage.as_mut(), "nobody", &[]).is_err());
+        assert!(enroll_identity(storage.as_mut(), "bad", &[vec![1.0, 0.0], vec![1.0]]).is_err());
+
+        // Magnitudes differ wildly, but each shot contributes equally after normalization
+        let id = enroll_identity(storage.as_mut(), "alice", &[vec![10.0, 0.0], vec![0.0, 0.1]])?;
+        let template = storage.get_embedding(&id)?.expect("template should be stored");
+        let expected = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(template.embedding.iter().all(|v| (v - expected).abs() < 1e-6));
+        assert_eq!(storage.get_all_embeddings()?.len(), 1);
+        assert!(is_normalized(&template));
+        Ok(())
+    }
+
+    #[test]
+    fn normalized_records_score_like_raw_ones() -> Result<()> {
+        let (mut raw, mut normalized) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let gallery = [vec![3.0, 4.0, 0.5], vec![-1.0, 0.2, 7.0], vec![0.1, 0.1, 0.1]];
+        for (i, embedding) in gallery.iter().enumerate() {
+            add_record(&mut raw, &format!("person_{i}"), embedding.clone())?;
+            add_record_normalized(&mut normalized, &format!("person_{i}"), embedding.clone())?;
+        }
+
+        let stored = normalized.get_all_embeddings()?;
+        assert!(stored.iter().all(|record| is_normalized(record) && (l2_norm(&record.embedding) - 1.0).abs() < 1e-6));
+        let raw_norm: f32 = stored.iter().map(|record| record.metadata[L2_NORM_KEY].parse::<f32>().unwrap()).sum();
+        assert!((raw_norm - gallery.iter().map(|e| l2_norm(e)).sum::<f32>()).abs() < 1e-4);
+
+        // The query is not normalized by the caller; search normalizes it for the dot product
+        let query = [2.0, 5.0, 1.0];
+        let (fast, slow) = (top_k(&normalized, &query, 3)?, top_k(&raw, &query, 3)?);
+        let names = |results: &[(EmbeddingRecord, f32)]| results.iter().map(|(r, _)| r.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&fast), names(&slow));
+        for ((_, dot), (_, cosine)) in fast.iter().zip(&slow) {
+            assert!((dot - cosine).abs() < 1e-6, "dot {} vs cosine {}", dot, cosine);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn soft_deleted_record_is_hidden_until_restored() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let alice = add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+        let bob = add_record(&mut storage, "bob", vec![0.0, 1.0])?;
+        let found = |storage: &InMemoryStorage| -> Result<Vec<String>> {
+            Ok(search_similar(storage, &[1.0, 0.2], 5)?.into_iter().map(|(r, _)| r.name).collect())
+        };
+
+        assert!(storage.soft_delete(&alice)?);
+        assert!(!storage.soft_delete(&alice)?, "Already soft-deleted");
+        assert_eq!(found(&storage)?, ["bob"]);
+        assert!(storage.get_embedding(&alice)?.is_some_and(|r| r.is_deleted()), "The record itself is kept");
+        assert_eq!(search_stream(&storage, &[1.0, 0.0], 0.5).count(), 0);
+
+        assert!(storage.restore(&alice)?);
+        assert!(!storage.restore(&alice)?);
+        assert_eq!(found(&storage)?, ["alice", "bob"]);
+
+        storage.soft_delete(&bob)?;
+        assert_eq!(storage.purge_deleted()?, 1);
+        assert!(!storage.contains(&bob)? && storage.contains(&alice)?);
+        assert!(!storage.soft_delete("missing")? && !storage.restore("missing")?);
+        Ok(())
+    }
+
+    fn enroll_brad_and_tom(storage: &mut dyn EmbeddingStorage, model: &candle_nn::Func) -> Result<()> {
+        add_record(storage, "brad", fixture_embedding(model, "../../../app/test_images/brad1.png")?)?;
+        add_record(storage, "tom", fixture_embedding(model, "../../../app/test_images/tom.png")?)?;
+        Ok(())
+    }
+
+    #[test]
+    fn identify_accepts_known_person() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        let model = build_model()?;
+        enroll_brad_and_tom(storage.as_mut(), &model)?;
+
+        let probe = fixture_embedding(&model, "../../../app/test_images/brad2.png")?;
+        let (record, similarity) = identify(storage.as_ref(), &probe, DEFAULT_MATCH_THRESHOLD)?
+            .expect("brad2 should be identified");
+        assert_eq!(record.name, "brad");
+        assert!(similarity >= DEFAULT_MATCH_THRESHOLD);
+        Ok(())
+    }
+
+    #[test]
+    fn identify_rejects_unknown_person() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        let model = build_model()?;
+        add_record(storage.as_mut(), "brad", fixture_embedding(&model, "../../../app/test_images/brad1.png")?)?;
+
+        let probe = fixture_embedding(&model, "../../../app/test_images/tom.png")?;
+        assert!(identify(storage.as_ref(), &probe, DEFAULT_MATCH_THRESHOLD)?.is_none());
+
+        add_record(storage.as_mut(), "tom", probe.clone())?;
+        let identified = identify(storage.as_ref(), &probe, DEFAULT_MATCH_THRESHOLD)?;
+        assert_eq!(identified.map(|(record, _)| record.name), Some("tom".to_string()));
+        Ok(())
+    }
+
+    // Wraps a storage and counts how often the full gallery is loaded
+    struct CountingStorage {
+        inner: Box<dyn EmbeddingStorage>,
+        loads: Cell<usize>,
+    }
+
+    impl EmbeddingStorage for CountingStorage {
+        fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+            self.inner.store_embedding(record)
+        }
+
+        fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+            self.inner.get_embedding(id)
+        }
+
+        fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+            self.loads.set(self.loads.get() + 1);
+            self.inner.get_all_embeddings()
+        }
+
+        fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+            self.inner.delete_embedding(id)
+        }
+
+        fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+            self.inner.update_embedding(record)
+        }
+    }
+
+    #[test]
+    fn batch_search_ranks_each_query_and_loads_gallery_once() -> Result<()> {
+        let (inner, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        let mut storage = CountingStorage { inner, loads: Cell::new(0) };
+
+        add_record(&mut storage, "alice", vec![1.0, 0.0, 0.0])?;
+        add_record(&mut storage, "bob", vec![0.0, 1.0, 0.0])?;
+        add_record(&mut storage, "carol", vec![0.0, 0.0, 1.0])?;
+
+        let queries = vec![vec![0.1, 1.0, 0.0], vec![1.0, 0.0, 0.2]];
+        let results = search_similar_batch(&storage, &queries, 2)?;
+        assert_eq!(storage.loads.get(), 1);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0][0].0.name, "bob");
+        assert_eq!(results[1][0].0.name, "alice");
+        for (query, batched) in queries.iter().zip(&results) {
+            assert_eq!(batched, &search_similar(&storage, query, 2)?);
+        }
+        assert!(search_similar_batch(&storage, &[], 2)?.is_empty());
+        Ok(())
+    }
+
+    // Stands in for a backend ranking in its database: every cosine search answers alice
+    struct RankingStorage {
+        inner: InMemoryStorage,
+        searches: Cell<usize>,
+    }
+
+    impl EmbeddingStorage for RankingStorage {
+        fn store_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+            self.inner.store_embedding(record)
+        }
+
+        fn get_embedding(&self, id: &str) -> Result<Option<EmbeddingRecord>> {
+            self.inner.get_embedding(id)
+        }
+
+        fn get_all_embeddings(&self) -> Result<Vec<EmbeddingRecord>> {
+            self.inner.get_all_embeddings()
+        }
+
+        fn delete_embedding(&mut self, id: &str) -> Result<bool> {
+            self.inner.delete_embedding(id)
+        }
+
+        fn update_embedding(&mut self, record: EmbeddingRecord) -> Result<()> {
+            self.inner.update_embedding(record)
+        }
+
+        fn nearest(&self, _query: &[f32], limit: usize) -> Result<Option<Vec<(EmbeddingRecord, f32)>>> {
+            self.searches.set(self.searches.get() + 1);
+            let records = self.inner.get_all_embeddings()?.into_iter();
+            Ok(Some(records.filter(|record| record.name == "alice").take(limit).map(|record| (record, 1.0)).collect()))
+        }
+    }
+
+    #[test]
+    fn cosine_searches_use_the_storage_ranking() -> Result<()> {
+        let mut storage = RankingStorage { inner: InMemoryStorage::new(), searches: Cell::new(0) };
+        let alice = add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+        add_record(&mut storage, "bob", vec![0.0, 1.0])?;
+
+        let probe = [0.0, 1.0];
+        assert_eq!(search_similar(&storage, &probe, 1)?[0].0.id, alice);
+        assert_eq!(top_k(&storage, &probe, 1)?[0].0.id, alice);
+        assert_eq!(identify(&storage, &probe, 0.5)?.map(|(record, _)| record.id), Some(alice));
+        assert_eq!(storage.searches.get(), 3);
+
+        // Other metrics are still scored here
+        assert_eq!(top_k_with_metric(&storage, &probe, 1, Some(Metric::Euclidean))?[0].0.name, "bob");
+        assert_eq!(storage.searches.get(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn add_records_returns_ids_in_input_order() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+
+        let entries: Vec<(String, Vec<f32>)> = (0..5).map(|i| (format!("person_{i}"), vec![i as f32, 1.0])).collect();
+        let ids = add_records(storage.as_mut(), entries.clone())?;
+        assert_eq!(ids.len(), 5);
+        for (id, (name, embedding)) in ids.iter().zip(&entries) {
+            let record = storage.get_embedding(id)?.expect("batch record should be stored");
+            assert_eq!(&record.name, name);
+            assert_eq!(&record.embedding, embedding);
+        }
+        assert!(add_records(storage.as_mut(), Vec::new())?.is_empty());
+        assert_eq!(storage.get_all_embeddings()?.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn shared_storage_supports_concurrent_enroll_and_search() -> Result<()> {
+        let storage = SharedStorage::new(InMemoryStorage::new());
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let mut storage = storage.clone();
+                std::thread::spawn(move || -> Result<()> {
+                    for i in 0..25 {
+                        let angle = (t * 25 + i) as f32 * 0.01;
+                        add_record(&mut storage, &format!("person_{t}_{i}"), vec![angle.cos(), angle.sin()])?;
+                        let results = search_similar(&storage, &[angle.cos(), angle.sin()], 3)?;
+                        assert!(!results.is_empty());
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().expect("worker thread panicked")?;
+        }
+        assert_eq!(storage.get_all_embeddings()?.len(), 200);
+        Ok(())
+    }
+
+    #[test]
+    fn shared_storage_shares_a_sqlite_connection_across_threads() -> Result<()> {
+        let path = format!("workshop_shared_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let storage = SharedStorage::new(SqliteStorage::new(&path)?);
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let mut storage = storage.clone();
+                std::thread::spawn(move || add_record(&mut storage, &format!("person_{t}"), vec![t as f32, 1.0]))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().expect("worker thread panicked")?;
+        }
+        assert_eq!(storage.count()?, 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn async_api_enrolls_and_searches() -> Result<()> {
+        let path = format!("workshop_async_{}.json", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let mut storage = BlockingAdapter::new(LocalFileStorage::new(path)?);
+
+        for (name, embedding) in [("alice", vec![1.0, 0.0]), ("bob", vec![0.0, 1.0])] {
+            storage
+                .store_embedding(EmbeddingRecord {
+                    id: Uuid::new_v4().to_string(),
+                    name: name.to_string(),
+                    embedding,
+                    created_at: chrono::Utc::now(),
+                    metadata: HashMap::new(),
+                    expires_at: None,
+                })
+                .await?;
+        }
+
+        let results = top_k_async(&storage, &[0.9, 0.1], 1).await?;
+        assert_eq!(results[0].0.name, "alice");
+        let bob = search_similar_async(&storage, &[0.0, 1.0], 2).await?[0].0.clone();
+        assert_eq!(bob.name, "bob");
+        assert!(storage.delete_embedding(&bob.id).await?);
+        assert!(storage.get_embedding(&bob.id).await?.is_none());
+        assert_eq!(storage.get_all_embeddings().await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn async_api_runs_on_sqlite() -> Result<()> {
+        let path = format!("workshop_async_{}.db", Uuid::new_v4());
+        let _guard = TempFileGuard { path: path.clone() };
+        let mut storage = BlockingAdapter::new(SqliteStorage::new(&path)?);
+        let mut record = new_record("alice".to_string(), vec![1.0, 0.0], chrono::Utc::now());
+        storage.store_embedding(record.clone()).await?;
+        record.name = "alice b".to_string();
+        storage.update_embedding(record.clone()).await?;
+        assert_eq!(top_k_async(&storage, &[0.9, 0.1], 1).await?[0].0.name, "alice b");
+        Ok(())
+    }
+
+    #[test]
+    fn mismatched_store_and_query_are_rejected() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        assert!(search_similar(storage.as_ref(), &[1.0; 128], 1)?.is_empty());
+
+        add_record(storage.as_mut(), "alice", vec![0.1; 512])?;
+        let err = add_record(storage.as_mut(), "bob", vec![0.1; 128]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FaceAuthError>(),
+            Some(FaceAuthError::DimensionMismatch { expected: 512, actual: 128 })
+        ));
+
+        let err = search_similar(storage.as_ref(), &[0.1; 128], 1).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FaceAuthError>(),
+            Some(FaceAuthError::DimensionMismatch { expected: 512, actual: 128 })
+        ));
+        assert!(top_k(storage.as_ref(), &[0.1; 513], 1).is_err());
+        assert_eq!(storage.dimension()?, Some(512));
+        Ok(())
+    }
+
+    #[test]
+    fn streamed_search_matches_full_sort_across_chunks() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let count = SCORING_CHUNK * 2 + 13;
+        for i in 0..count {
+            let angle = (i % 97) as f32 * 0.05;
+            add_record(&mut storage, &format!("person_{i}"), vec![angle.cos(), angle.sin()])?;
+        }
+        let query = vec![0.3, 0.9];
+
+        // Reference: materialize everything, score and fully sort
+        let mut expected: Vec<(EmbeddingRecord, f32)> = storage
+            .iter_embeddings()
+            .map(|record| {
+                let record = record?;
+                let similarity = cosine_similarity_vec(&query, &record.embedding)?;
+                Ok((record, similarity))
+            })
+            .collect::<Result<_>>()?;
+        expected.sort_by(by_rank);
+
+        for limit in [1, 10, SCORING_CHUNK + 5, count] {
+            let streamed = search_similar(&storage, &query, limit)?;
+            assert_eq!(streamed.len(), limit);
+            assert_eq!(streamed, expected[..limit].to_vec(), "Mismatch for limit {}", limit);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn live_records_after_a_chunk_of_skipped_ones_are_scored() -> Result<()> {
+        let now = chrono::Utc::now();
+        let mut records: Vec<EmbeddingRecord> = (0..SCORING_CHUNK + 10)
+            .map(|i| {
+                let mut record = new_record(format!("gone_{i}"), vec![1.0, 0.0], now);
+                if i % 2 == 0 {
+                    record.expires_at = Some(now - chrono::Duration::minutes(1));
+                } else {
+                    record.metadata.insert(ex04_storage_local_solution::DELETED_KEY.to_string(), now.to_rfc3339());
+                }
+                record
+            })
+            .collect();
+        records.push(new_record("alice".to_string(), vec![0.9, 0.1], now));
+
+        let results = rank_records(records.into_iter().map(Ok), &[1.0, 0.0], 5, Metric::Cosine)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0.name, "alice");
+        Ok(())
+    }
+
+    #[test]
+    fn grouped_top_k_returns_each_person_once() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+        add_record(&mut storage, "alice", vec![0.99, 0.1])?;
+        add_record(&mut storage, "alice", vec![0.98, 0.15])?;
+        add_record(&mut storage, "bob", vec![0.6, 0.8])?;
+        add_record(&mut storage, "", vec![0.5, 0.85])?;
+        add_record(&mut storage, "", vec![0.4, 0.9])?;
+
+        let query = [1.0, 0.05];
+        let plain: Vec<String> = top_k(&storage, &query, 2)?.into_iter().map(|(r, _)| r.name).collect();
+        assert_eq!(plain, vec!["alice", "alice"]);
+
+        let grouped = top_k_by_name(&storage, &query, 2)?;
+        let names: Vec<&str> = grouped.iter().map(|(r, _)| r.name.as_str()).collect();
+        assert_eq!(names, vec!["alice", "bob"]);
+        assert_eq!(grouped[0].0.embedding, vec![1.0, 0.0]);
+
+        // Unnamed records are not collapsed into one group
+        assert_eq!(top_k_by_name(&storage, &query, 10)?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn knn_vote_beats_single_closest_intruder() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        add_record(&mut storage, "intruder", vec![1.0, 0.05])?;
+        add_record(&mut storage, "alice", vec![0.95, 0.3])?;
+        add_record(&mut storage, "alice", vec![0.9, 0.4])?;
+        add_record(&mut storage, "bob", vec![0.0, 1.0])?;
+
+        let query = [1.0, 0.0];
+        assert_eq!(identify(&storage, &query, 0.5)?.map(|(r, _)| r.name), Some("intruder".to_string()));
+        assert_eq!(classify_knn(&storage, &query, 3, 1.0)?, Some("alice".to_string()));
+        assert_eq!(classify_knn(&storage, &query, 3, 2.0)?, None);
+        assert_eq!(classify_knn(&storage, &query, 1, 0.5)?, Some("intruder".to_string()));
+        assert_eq!(classify_knn(&InMemoryStorage::new(), &query, 3, 0.0)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn each_metric_ranks_fixtures_in_its_direction() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let model = build_model()?;
+        add_record(&mut storage, "brad", fixture_embedding(&model, "../../../app/test_images/brad1.png")?)?;
+        add_record(&mut storage, "tom", fixture_embedding(&model, "../../../app/test_images/tom.png")?)?;
+        let probe = fixture_embedding(&model, "../../../app/test_images/brad2.png")?;
+
+        for metric in [Metric::Cosine, Metric::Euclidean, Metric::Dot] {
+            let results = top_k_with_metric(&storage, &probe, 2, Some(metric))?;
+            assert_eq!(results[0].0.name, "brad", "{:?} should rank brad first", metric);
+            if metric.higher_is_better() {
+                assert!(results[0].1 >= results[1].1);
+            } else {
+                assert!(results[0].1 <= results[1].1);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn euclidean_metric_sorts_by_ascending_distance() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        add_record(&mut storage, "far", vec![-1.0, 0.0])?;
+        add_record(&mut storage, "near", vec![1.0, 0.1])?;
+        add_record(&mut storage, "middle", vec![0.0, 1.0])?;
+
+        let query = [1.0, 0.0];
+        let results = top_k_with_metric(&storage, &query, 3, Some(Metric::Euclidean))?;
+        let names: Vec<&str> = results.iter().map(|(r, _)| r.name.as_str()).collect();
+        assert_eq!(names, vec!["near", "middle", "far"]);
+        assert!(results.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert!((results[2].1 - 2.0).abs() < 1e-6);
+
+        assert_eq!(top_k_with_metric(&storage, &query, 3, None)?, top_k(&storage, &query, 3)?);
+        Ok(())
+    }
+
+    #[test]
+    fn time_window_excludes_records_outside_it() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let now = Utc::now();
+        for (name, days_ago) in [("old", 90), ("recent", 10), ("today", 0)] {
+            storage.store_embedding(EmbeddingRecord {
+                id: Uuid::new_v4().to_string(),
+                name: name.to_string(),
+                embedding: vec![1.0, days_ago as f32 * 0.01],
+                created_at: now - chrono::Duration::days(days_ago),
+                metadata: HashMap::new(),
+                expires_at: None,
+            })?;
+        }
+        let names = |results: Vec<(EmbeddingRecord, f32)>| -> Vec<String> {
+            let mut names: Vec<String> = results.into_iter().map(|(record, _)| record.name).collect();
+            names.sort();
+            names
+        };
+        let query = [1.0, 0.0];
+
+        let last_30_days = search_similar_since(&storage, &query, 10, now - chrono::Duration::days(30))?;
+        assert_eq!(names(last_30_days), vec!["recent", "today"]);
+        let before_last_week = search_similar_until(&storage, &query, 10, now - chrono::Duration::days(7))?;
+        assert_eq!(names(before_last_week), vec!["old", "recent"]);
+        let window = search_similar_between(
+            &storage,
+            &query,
+            10,
+            Some(now - chrono::Duration::days(30)),
+            Some(now - chrono::Duration::days(7)),
+        )?;
+        assert_eq!(names(window), vec!["recent"]);
+        assert_eq!(search_similar_between(&storage, &query, 10, None, None)?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn collection_search_never_crosses_collections() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        let record = |name: &str, embedding: Vec<f32>| EmbeddingRecord {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            embedding,
+            created_at: Utc::now(),
+            metadata: HashMap::new(),
+            expires_at: None,
+        };
+        storage.store_embedding_in("employees", record("alice", vec![0.8, 0.2]))?;
+        storage.store_embedding_in("employees", record("bob", vec![0.0, 1.0]))?;
+        storage.store_embedding_in("visitors", record("visitor", vec![1.0, 0.0]))?;
+        add_record(storage.as_mut(), "legacy", vec![1.0, 0.01])?;
+
+        let query = [1.0, 0.0];
+        let employees = search_similar_in(storage.as_ref(), "employees", &query, 10)?;
+        assert_eq!(employees.len(), 2);
+        assert_eq!(employees[0].0.name, "alice");
+        assert!(employees.iter().all(|(r, _)| r.collection() == "employees"));
+
+        let visitors = search_similar_in(storage.as_ref(), "visitors", &query, 10)?;
+        assert_eq!(visitors.len(), 1);
+        assert_eq!(visitors[0].0.name, "visitor");
+
+        // Records stored without a collection live in the default one
+        let default = search_similar_in(storage.as_ref(), DEFAULT_COLLECTION, &query, 10)?;
+        assert_eq!(default.len(), 1);
+        assert_eq!(default[0].0.name, "legacy");
+        assert!(storage.get_all_embeddings_in("contractors")?.is_empty());
+        assert_eq!(storage.get_all_embeddings()?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn expired_records_are_skipped_then_purged() -> Result<()> {
+        let (mut storage, path) = open_temp_storage()?;
+        let _guard = TempFileGuard { path };
+        let employee = add_record(storage.as_mut(), "employee", vec![0.9, 0.1])?;
+        let visitor = EmbeddingRecord {
+            id: Uuid::new_v4().to_string(),
+            name: "visitor".to_string(),
+            embedding: vec![1.0, 0.0],
+            created_at: Utc::now() - chrono::Duration::days(2),
+            metadata: HashMap::new(),
+            expires_at: Some(Utc::now() - chrono::Duration::days(1)),
+        };
+        storage.store_embedding(visitor.clone())?;
+
+        let results = search_similar(storage.as_ref(), &[1.0, 0.0], 10)?;
+        assert_eq!(results.len(), 1, "Expired badge should not be searchable");
+        assert_eq!(results[0].0.id, employee);
+        assert!(identify(storage.as_ref(), &[1.0, 0.0], 0.999)?.is_none());
+        assert!(storage.get_embedding(&visitor.id)?.is_some(), "Still stored until purged");
+
+        assert_eq!(storage.purge_expired()?, 1);
+        assert_eq!(storage.get_embedding(&visitor.id)?, None);
+        assert_eq!(storage.get_all_embeddings()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn identifications_are_written_to_the_audit_log() -> Result<()> {
+        let log_path = format!("workshop_audit_{}.jsonl", Uuid::new_v4());
+        let _log_guard = TempFileGuard { path: log_path.clone() };
+        let log = JsonlAuditLog::open(&log_path)?;
+
+        let mut storage = InMemoryStorage::new();
+        let alice = add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+        assert!(identify_audited(&storage, &[1.0, 0.05], 0.9, Some(&log))?.is_some());
+        assert!(identify_audited(&storage, &[0.0, 1.0], 0.9, Some(&log))?.is_none());
+
+        let lines: Vec<AuthAttempt> = std::fs::read_to_string(&log_path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].passed);
+        assert!(!lines[1].passed, "A rejected attempt still names the closest candidate");
+        for attempt in &lines {
+            assert_eq!(attempt.candidate_id.as_deref(), Some(alice.as_str()));
+            assert_eq!(attempt.candidate_name.as_deref(), Some("alice"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rate_limited_identify_stops_at_burst() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+        let limiter = RateLimiter::new(0.0, 1);
+
+        assert!(identify_rate_limited(&storage, &[1.0, 0.0], 0.9, &limiter, "10.0.0.1", None)?.is_some());
+        let err = identify_rate_limited(&storage, &[1.0, 0.0], 0.9, &limiter, "10.0.0.1", None).unwrap_err();
+        assert!(matches!(err.downcast_ref::<FaceAuthError>(), Some(FaceAuthError::RateLimited(_))));
+        assert!(identify_rate_limited(&storage, &[1.0, 0.0], 0.9, &limiter, "10.0.0.2", None)?.is_some());
+        Ok(())
+    }
+
+    struct FixedLiveness(f32);
+
+    impl Liveness for FixedLiveness {
+        fn check(&self, _img: &Tensor) -> Result<f32> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn identify_live_rejects_spoofed_probe() -> Result<()> {
+        let log_path = format!("workshop_audit_{}.jsonl", Uuid::new_v4());
+        let _log_guard = TempFileGuard { path: log_path.clone() };
+        let log = JsonlAuditLog::open(&log_path)?;
+        let mut storage = InMemoryStorage::new();
+        add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+        let image = Tensor::zeros((3, 224, 224), candle_core::DType::F32, &candle_core::Device::Cpu)?;
+
+        let live = identify_live(&storage, &[1.0, 0.0], &image, 0.9, &FixedLiveness(0.8), 0.5, Some(&log))?;
+        assert_eq!(live.map(|(record, _)| record.name).as_deref(), Some("alice"));
+        let spoof = identify_live(&storage, &[1.0, 0.0], &image, 0.9, &FixedLiveness(0.2), 0.5, Some(&log))?;
+        assert!(spoof.is_none(), "A matching face below the liveness threshold is rejected");
+
+        let lines: Vec<AuthAttempt> = std::fs::read_to_string(&log_path)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert!(lines[0].passed);
+        assert!(!lines[1].passed && lines[1].candidate_id.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn enrollment_records_the_raw_l2_norm() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let stored_norm = |storage: &InMemoryStorage, id: &str| -> Result<f32> {
+            let record = storage.get_embedding(id)?.expect("record was stored");
+            Ok(record.metadata[L2_NORM_KEY].parse::<f32>()?)
+        };
+
+        let id = add_record(&mut storage, "alice", vec![3.0, 4.0, 12.0])?;
+        assert!((stored_norm(&storage, &id)? - 13.0).abs() < 1e-6);
+
+        let ids = add_records(&mut storage, vec![("bob".to_string(), vec![0.6, 0.8, 0.0])])?;
+        assert!((stored_norm(&storage, &ids[0])? - 1.0).abs() < 1e-6);
+
+        let shots = vec![vec![2.0, 0.0, 0.0], vec![0.0, 4.0, 0.0]];
+        let id = enroll_identity(&mut storage, "carol", &shots)?;
+        assert!((stored_norm(&storage, &id)? - 3.0).abs() < 1e-6, "Template keeps the mean shot norm");
+        assert_eq!(storage.get_embedding(&id)?.unwrap().metadata["source_images"], "2");
+        Ok(())
+    }
+
+    #[test]
+    fn bulk_enrollment_reports_progress_after_each_record() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let entries: Vec<(String, Vec<f32>)> = (0..5).map(|i| (format!("person_{i}"), vec![i as f32, 1.0])).collect();
+
+        let mut progress = Vec::new();
+        let ids = add_records_with_progress(&mut storage, entries, |done, total| progress.push((done, total)))?;
+        assert_eq!(progress, vec![(1, 5), (2, 5), (3, 5), (4, 5), (5, 5)]);
+        assert_eq!(ids.len(), 5);
+        assert_eq!(storage.get_all_embeddings()?.len(), 5);
+
+        let mut calls = 0;
+        add_records_with_progress(&mut storage, Vec::new(), |_, _| calls += 1)?;
+        assert_eq!(calls, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn search_stream_yields_exactly_the_records_above_the_cutoff() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        let mut above = HashSet::new();
+        for i in 0..20 {
+            let angle = i as f32 * 0.15;
+            let id = add_record(&mut storage, &format!("person_{i}"), vec![angle.cos(), angle.sin()])?;
+            if angle.cos() >= 0.8 {
+                above.insert(id);
+            }
+        }
+
+        let mut streamed = search_stream(&storage, &[1.0, 0.0], 0.8);
+        let first = streamed.next().expect("some records qualify")?;
+        assert!(first.1 >= 0.8);
+        let mut seen: HashSet<String> = HashSet::from([first.0.id]);
+        for result in streamed {
+            let (record, similarity) = result?;
+            assert!(similarity >= 0.8, "{} scored {}", record.name, similarity);
+            seen.insert(record.id);
+        }
+        assert_eq!(seen, above);
+
+        let mut mismatched = search_stream(&storage, &[1.0, 0.0, 0.0], 0.0);
+        assert!(mismatched.next().is_some_and(|result| result.is_err()));
+        Ok(())
+    }
+}
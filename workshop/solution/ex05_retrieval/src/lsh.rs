@@ -0,0 +1,133 @@
+use face_auth_error::FaceAuthError;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// One bit per hyperplane, packed into a u64
+pub const SIGNATURE_BITS: usize = 64;
+// Default seed, so `sign_hash` signatures stay comparable across calls and runs
+const HYPERPLANE_SEED: u64 = 0x15a_c0de;
+
+// Random-hyperplane LSH (SimHash): the Hamming distance between two signatures estimates the
+// angle between the embeddings, so it can cheaply pre-filter candidates before exact cosine.
+// Signatures are only comparable between embeddings hashed with the same hyperplanes.
+pub struct Lsh {
+    dims: usize,
+    // SIGNATURE_BITS normals, each `dims` long, stored back to back
+    planes: Vec<f32>,
+}
+
+impl Lsh {
+    pub fn new(dims: usize) -> Self {
+        Self::with_seed(dims, HYPERPLANE_SEED)
+    }
+
+    // Hyperplanes drawn from `seed`; the same seed and dimension always give the same signatures
+    pub fn with_seed(dims: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        // Gaussian entries (Box-Muller) so the normals are spread evenly over every direction
+        let planes = (0..SIGNATURE_BITS * dims)
+            .map(|_| {
+                let u1: f32 = rng.random_range(f32::MIN_POSITIVE..1.0);
+                let u2: f32 = rng.random();
+                (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+            })
+            .collect();
+        Lsh { dims, planes }
+    }
+
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    // Bit i is set when the embedding lies on the positive side of hyperplane i
+    pub fn sign(&self, embedding: &[f32]) -> Result<u64, FaceAuthError> {
+        if embedding.len() != self.dims {
+            return Err(FaceAuthError::DimensionMismatch {
+                expected: self.dims,
+                actual: embedding.len(),
+            });
+        }
+        Ok(self.signature(embedding))
+    }
+
+    fn signature(&self, embedding: &[f32]) -> u64 {
+        if self.dims == 0 {
+            return 0;
+        }
+        self.planes
+            .chunks_exact(self.dims)
+            .enumerate()
+            .fold(0u64, |bits, (i, plane)| {
+                let projection: f32 = plane.iter().zip(embedding).map(|(p, x)| p * x).sum();
+                if projection > 0.0 { bits | (1 << i) } else { bits }
+            })
+    }
+}
+
+// 64-bit signature of `embedding` using the default hyperplanes for its dimension.
+// Regenerates the hyperplanes on every call; build one `Lsh` when hashing a whole gallery.
+pub fn sign_hash(embedding: &[f32]) -> u64 {
+    Lsh::new(embedding.len()).signature(embedding)
+}
+
+// Number of differing bits; 0 means same side of every hyperplane, 64 means opposite
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use ex01_image_processing_solution::imagenet::load_image224;
+    use ex02_embeddings_solution::{build_model, compute_embedding};
+
+    fn fixture_embedding(model: &candle_nn::Func, path: &str) -> Result<Vec<f32>> {
+        let image = load_image224(path)?;
+        Ok(compute_embedding(model, &image)?.squeeze(0)?.to_vec1::<f32>()?)
+    }
+
+    #[test]
+    fn same_person_signatures_are_closer() -> Result<()> {
+        let model = build_model()?;
+        let brad1 = fixture_embedding(&model, "../../../app/test_images/brad1.png")?;
+        let brad2 = fixture_embedding(&model, "../../../app/test_images/brad2.png")?;
+        let tom = fixture_embedding(&model, "../../../app/test_images/tom.png")?;
+
+        let lsh = Lsh::new(brad1.len());
+        let (b1, b2, t) = (lsh.sign(&brad1)?, lsh.sign(&brad2)?, lsh.sign(&tom)?);
+        assert_eq!(sign_hash(&brad1), b1);
+        assert!(
+            hamming(b1, b2) < hamming(b1, t),
+            "brad/brad {} vs brad/tom {}",
+            hamming(b1, b2),
+            hamming(b1, t)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn hamming_tracks_direction() -> Result<()> {
+        let v = [0.4, -1.0, 2.5, 0.3];
+        let opposite: Vec<f32> = v.iter().map(|x| -x).collect();
+        let scaled: Vec<f32> = v.iter().map(|x| x * 3.0).collect();
+
+        assert_eq!(hamming(sign_hash(&v), sign_hash(&scaled)), 0);
+        assert_eq!(hamming(sign_hash(&v), sign_hash(&opposite)), SIGNATURE_BITS as u32);
+        assert_eq!(hamming(0b1011, 0b0110), 3);
+        assert!(matches!(
+            Lsh::new(4).sign(&[1.0, 2.0]),
+            Err(FaceAuthError::DimensionMismatch { expected: 4, actual: 2 })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn same_seed_gives_identical_signatures() -> Result<()> {
+        let v: Vec<f32> = (0..32).map(|i| (i as f32 * 0.7).sin()).collect();
+        assert_eq!(Lsh::with_seed(32, 9).sign(&v)?, Lsh::with_seed(32, 9).sign(&v)?);
+        assert_eq!(Lsh::new(32).sign(&v)?, sign_hash(&v));
+        assert_ne!(Lsh::with_seed(32, 9).sign(&v)?, Lsh::with_seed(32, 10).sign(&v)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::Result;
+use chrono::Utc;
+use ex03_similarity_solution::{dot_similarity_vec, normalize_l2_vec};
+use ex04_storage_local_solution::EmbeddingStorage;
+use std::collections::HashSet;
+
+// The two most similar records belonging to different people
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfusablePair {
+    pub id_a: String,
+    pub name_a: String,
+    pub id_b: String,
+    pub name_b: String,
+    pub similarity: f32,
+}
+
+// Sanity summary of a gallery, see `gallery_report`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GalleryReport {
+    pub records: usize,
+    pub distinct_names: usize,
+    // Mean cosine similarity over pairs of records with the same name; `None` when nobody
+    // has more than one record
+    pub mean_intra_name_similarity: Option<f32>,
+    // The closest pair of records with different names, `None` with fewer than two people.
+    // A similarity near the match threshold means those two can be mistaken for each other.
+    pub closest_inter_name_pair: Option<ConfusablePair>,
+}
+
+// Summarize the searchable gallery: expired and soft-deleted records are left out, as search
+// leaves them out. As in `top_k_by_name`, each unnamed record counts as a person of its own.
+// Compares all pairs, so this is meant for offline checks before deploying. Pairs are scored one
+// at a time against running totals, so memory stays linear in the gallery size.
+pub fn gallery_report(storage: &dyn EmbeddingStorage) -> Result<GalleryReport> {
+    let now = Utc::now();
+    let mut records: Vec<_> = storage
+        .get_all_embeddings()?
+        .into_iter()
+        .filter(|record| !record.is_expired_at(now) && !record.is_deleted())
+        .collect();
+    // A fixed order so ties always flag the same pair
+    records.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+
+    // Normalized once, so each pair's cosine similarity is a dot product
+    let units: Vec<Vec<f32>> = records.iter().map(|record| normalize_l2_vec(&record.embedding)).collect();
+    let same_person = |i: usize, j: usize| !records[i].name.is_empty() && records[i].name == records[j].name;
+
+    let (mut intra_total, mut intra_pairs) = (0.0, 0usize);
+    let mut closest: Option<(usize, usize, f32)> = None;
+    for (i, a) in units.iter().enumerate() {
+        for (j, b) in units.iter().enumerate().skip(i + 1) {
+            let similarity = dot_similarity_vec(a, b)?;
+            if same_person(i, j) {
+                intra_total += similarity;
+                intra_pairs += 1;
+            } else if closest.is_none_or(|(_, _, best)| similarity > best) {
+                closest = Some((i, j, similarity));
+            }
+        }
+    }
+
+    let named: HashSet<&str> = records.iter().map(|r| r.name.as_str()).filter(|name| !name.is_empty()).collect();
+    let unnamed = records.iter().filter(|r| r.name.is_empty()).count();
+    Ok(GalleryReport {
+        records: records.len(),
+        distinct_names: named.len() + unnamed,
+        mean_intra_name_similarity: (intra_pairs > 0).then(|| intra_total / intra_pairs as f32),
+        closest_inter_name_pair: closest.map(|(i, j, similarity)| ConfusablePair {
+            id_a: records[i].id.clone(),
+            name_a: records[i].name.clone(),
+            id_b: records[j].id.clone(),
+            name_b: records[j].name.clone(),
+            similarity,
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_record;
+    use ex04_storage_local_solution::InMemoryStorage;
+
+    #[test]
+    fn report_flags_the_planted_lookalikes() -> Result<()> {
+        let mut storage = InMemoryStorage::new();
+        add_record(&mut storage, "alice", vec![1.0, 0.0, 0.0])?;
+        add_record(&mut storage, "alice", vec![0.9, 0.1, 0.0])?;
+        add_record(&mut storage, "bob", vec![0.0, 1.0, 0.0])?;
+        // Carol's template sits almost on top of Dave's
+        let carol = add_record(&mut storage, "carol", vec![0.0, 0.1, 1.0])?;
+        let dave = add_record(&mut storage, "dave", vec![0.0, 0.12, 0.98])?;
+        let erin = add_record(&mut storage, "erin", vec![0.0, 0.1, 1.0])?;
+        storage.soft_delete(&erin)?;
+
+        let report = gallery_report(&storage)?;
+        assert_eq!(report.records, 5);
+        assert_eq!(report.distinct_names, 4);
+        let intra = report.mean_intra_name_similarity.expect("alice has two records");
+        assert!((intra - ex03_similarity_solution::cosine_similarity_vec(&[1.0, 0.0, 0.0], &[0.9, 0.1, 0.0])?).abs() < 1e-6);
+
+        let pair = report.closest_inter_name_pair.expect("several people are enrolled");
+        let mut flagged = [pair.id_a.as_str(), pair.id_b.as_str()];
+        flagged.sort();
+        let mut planted = [carol.as_str(), dave.as_str()];
+        planted.sort();
+        assert_eq!(flagged, planted);
+        assert!(pair.similarity > 0.99);
+
+        let empty = gallery_report(&InMemoryStorage::new())?;
+        assert_eq!((empty.records, empty.distinct_names), (0, 0));
+        assert!(empty.mean_intra_name_similarity.is_none() && empty.closest_inter_name_pair.is_none());
+        Ok(())
+    }
+}
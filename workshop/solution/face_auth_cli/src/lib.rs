@@ -0,0 +1,3 @@
+// Pieces of the `face-auth` binary that are also usable as a library
+#[cfg(feature = "server")]
+pub mod server;
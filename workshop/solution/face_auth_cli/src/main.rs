@@ -0,0 +1,138 @@
+use anyhow::Result;
+use candle_core::Tensor;
+use clap::{Parser, Subcommand};
+use ex01_image_processing_solution::imagenet::load_image224;
+use ex02_embeddings_solution::{build_model, compute_embedding};
+use ex03_similarity_solution::verification::{verify, DEFAULT_MATCH_THRESHOLD};
+use ex04_storage_local_solution::SqliteStorage;
+use ex05_retrieval_solution::{add_record, identify};
+use serde_json::json;
+use std::process::ExitCode;
+
+// Exit status when verify finds no match or identify no known face; errors exit with 2
+const EXIT_NO_MATCH: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+#[derive(Parser)]
+#[command(name = "face-auth", about = "Enroll, verify and identify faces from the command line")]
+struct Cli {
+    /// SQLite gallery used by enroll, identify and serve
+    #[arg(long, global = true, default_value = "face_auth.db")]
+    db: String,
+    /// Cosine similarity needed to count as the same person
+    #[arg(long, global = true, default_value_t = DEFAULT_MATCH_THRESHOLD)]
+    threshold: f32,
+    /// Print results as JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Add a face to the gallery under a name
+    Enroll {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        image: String,
+    },
+    /// Check whether two photos show the same person
+    Verify {
+        #[arg(long)]
+        image_a: String,
+        #[arg(long)]
+        image_b: String,
+    },
+    /// Find who in the gallery a photo shows
+    Identify {
+        #[arg(long)]
+        image: String,
+    },
+    /// Serve the enroll, identify and gallery endpoints over HTTP
+    #[cfg(feature = "server")]
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: std::net::SocketAddr,
+        /// Models kept loaded; further requests wait for one to be free
+        #[arg(long, default_value_t = 2)]
+        models: usize,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(EXIT_NO_MATCH),
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+// Ok(false) means the command ran but found no match
+fn run(cli: &Cli) -> Result<bool> {
+    match &cli.command {
+        Command::Enroll { name, image } => {
+            let embedding = embed(&[image])?.remove(0);
+            let mut storage = SqliteStorage::new(&cli.db)?;
+            let id = add_record(&mut storage, name, embedding.flatten_all()?.to_vec1::<f32>()?)?;
+            if cli.json {
+                println!("{}", json!({ "id": id, "name": name }));
+            } else {
+                println!("Enrolled {name} as {id}");
+            }
+            Ok(true)
+        }
+        Command::Verify { image_a, image_b } => {
+            let embeddings = embed(&[image_a, image_b])?;
+            let decision = verify(&embeddings[0], &embeddings[1], cli.threshold)?;
+            if cli.json {
+                println!(
+                    "{}",
+                    json!({ "match": decision.is_match, "similarity": decision.similarity, "threshold": decision.threshold })
+                );
+            } else {
+                let verdict = if decision.is_match { "Match" } else { "No match" };
+                println!("{verdict} (similarity {:.3}, threshold {:.3})", decision.similarity, decision.threshold);
+            }
+            Ok(decision.is_match)
+        }
+        Command::Identify { image } => {
+            let query = embed(&[image])?.remove(0).flatten_all()?.to_vec1::<f32>()?;
+            let storage = SqliteStorage::new(&cli.db)?;
+            let found = identify(&storage, &query, cli.threshold)?;
+            match (&found, cli.json) {
+                (Some((record, similarity)), true) => {
+                    println!("{}", json!({ "id": record.id, "name": record.name, "similarity": similarity }))
+                }
+                (Some((record, similarity)), false) => println!("{} (similarity {similarity:.3})", record.name),
+                (None, true) => println!("{}", json!({ "id": null, "name": null })),
+                (None, false) => println!("Unknown face"),
+            }
+            Ok(found.is_some())
+        }
+        #[cfg(feature = "server")]
+        Command::Serve { addr, models } => {
+            use face_auth_cli::server::{serve, AppState};
+            let storage = SqliteStorage::new(&cli.db)?;
+            let state = AppState::new(ex02_embeddings_solution::ModelPool::new(*models)?, storage, cli.threshold);
+            println!("Listening on http://{addr}");
+            tokio::runtime::Runtime::new()?.block_on(serve(*addr, state))?;
+            Ok(true)
+        }
+    }
+}
+
+// Images are loaded before the model, so a bad path fails without downloading weights
+fn embed(paths: &[&String]) -> Result<Vec<Tensor>> {
+    let images = paths
+        .iter()
+        .map(|path| load_image224(path))
+        .collect::<Result<Vec<_>, _>>()?;
+    let model = build_model()?;
+    images.iter().map(|image| Ok(compute_embedding(&model, image)?)).collect()
+}
@@ -0,0 +1,167 @@
+use anyhow::Result;
+use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use ex01_image_processing_solution::imagenet::load_image224_from_bytes;
+use ex02_embeddings_solution::{compute_embedding, ModelPool};
+use ex04_storage_local_solution::{EmbeddingStorage, SharedStorage};
+use ex05_retrieval_solution::{add_record, identify};
+use face_auth_error::FaceAuthError;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+// Uploads larger than this are rejected before they are decoded
+const MAX_UPLOAD_BYTES: usize = 16 * 1024 * 1024;
+
+// Everything a request handler needs; cloned into every request, all clones share one gallery
+// and one model pool, so at most `pool.size()` uploads are embedded at once
+pub struct AppState<S: EmbeddingStorage> {
+    models: Arc<ModelPool>,
+    storage: SharedStorage<S>,
+    threshold: f32,
+}
+
+impl<S: EmbeddingStorage> Clone for AppState<S> {
+    fn clone(&self) -> Self {
+        AppState {
+            models: Arc::clone(&self.models),
+            storage: self.storage.clone(),
+            threshold: self.threshold,
+        }
+    }
+}
+
+impl<S: EmbeddingStorage> AppState<S> {
+    pub fn new(models: ModelPool, storage: S, threshold: f32) -> Self {
+        AppState {
+            models: Arc::new(models),
+            storage: SharedStorage::new(storage),
+            threshold,
+        }
+    }
+}
+
+// `POST /enroll` and `POST /identify` take a multipart form with an `image` file (and a `name`
+// field for enroll); `GET /gallery` lists enrolled records without their embeddings
+pub fn router<S: EmbeddingStorage + Send + Sync + 'static>(state: AppState<S>) -> Router {
+    Router::new()
+        .route("/enroll", post(enroll::<S>))
+        .route("/identify", post(identify_face::<S>))
+        .route("/gallery", get(gallery::<S>))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES))
+        .with_state(state)
+}
+
+pub async fn serve<S: EmbeddingStorage + Send + Sync + 'static>(addr: SocketAddr, state: AppState<S>) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, router(state)).await?;
+    Ok(())
+}
+
+// An error response with a JSON `{"error": ...}` body
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+// Undecodable uploads are the client's fault; anything else is ours
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast_ref::<FaceAuthError>() {
+            Some(FaceAuthError::ImageLoad { .. }) => ApiError(StatusCode::BAD_REQUEST, format!("{e:#}")),
+            _ => ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")),
+        }
+    }
+}
+
+fn bad_request(message: impl Into<String>) -> ApiError {
+    ApiError(StatusCode::BAD_REQUEST, message.into())
+}
+
+// Pull the `image` bytes and the optional `name` text out of a multipart form
+async fn read_form(mut form: Multipart) -> Result<(Vec<u8>, Option<String>), ApiError> {
+    let (mut image, mut name) = (None, None);
+    while let Some(field) = form.next_field().await.map_err(|e| bad_request(e.body_text()))? {
+        match field.name() {
+            Some("image") => image = Some(field.bytes().await.map_err(|e| bad_request(e.body_text()))?.to_vec()),
+            Some("name") => name = Some(field.text().await.map_err(|e| bad_request(e.body_text()))?),
+            _ => {}
+        }
+    }
+    let image = image.ok_or_else(|| bad_request("Missing 'image' field"))?;
+    Ok((image, name))
+}
+
+// Decoding and the forward pass are CPU-bound, so they run off the async executor. The model is
+// acquired after decoding, so a slow upload never holds one.
+async fn embed<S: EmbeddingStorage>(state: &AppState<S>, image: Vec<u8>) -> Result<Vec<f32>, ApiError> {
+    let models = Arc::clone(&state.models);
+    let embedding = tokio::task::spawn_blocking(move || -> Result<Vec<f32>> {
+        let tensor = load_image224_from_bytes(&image)?;
+        let model = models.acquire();
+        Ok(compute_embedding(&model, &tensor)?.flatten_all()?.to_vec1::<f32>()?)
+    })
+    .await
+    .map_err(anyhow::Error::from)??;
+    Ok(embedding)
+}
+
+// Storage calls block on file or database I/O (and on each other), so they run off the executor too
+async fn with_storage<S, T>(
+    state: &AppState<S>,
+    op: impl FnOnce(SharedStorage<S>) -> Result<T> + Send + 'static,
+) -> Result<T, ApiError>
+where
+    S: EmbeddingStorage + Send + Sync + 'static,
+    T: Send + 'static,
+{
+    let storage = state.storage.clone();
+    Ok(tokio::task::spawn_blocking(move || op(storage))
+        .await
+        .map_err(anyhow::Error::from)??)
+}
+
+async fn enroll<S: EmbeddingStorage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>,
+    form: Multipart,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let (image, name) = read_form(form).await?;
+    let name = name.filter(|n| !n.trim().is_empty()).ok_or_else(|| bad_request("Missing 'name' field"))?;
+    let embedding = embed(&state, image).await?;
+    let enrolled = name.clone();
+    let id = with_storage(&state, move |mut storage| add_record(&mut storage, &enrolled, embedding)).await?;
+    Ok((StatusCode::CREATED, Json(json!({ "id": id, "name": name }))))
+}
+
+async fn identify_face<S: EmbeddingStorage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>,
+    form: Multipart,
+) -> Result<Json<Value>, ApiError> {
+    let (image, _) = read_form(form).await?;
+    let query = embed(&state, image).await?;
+    let threshold = state.threshold;
+    match with_storage(&state, move |storage| identify(&storage, &query, threshold)).await? {
+        Some((record, similarity)) => Ok(Json(json!({ "id": record.id, "name": record.name, "similarity": similarity }))),
+        None => Err(ApiError(StatusCode::NOT_FOUND, "No enrolled face matches".to_string())),
+    }
+}
+
+async fn gallery<S: EmbeddingStorage + Send + Sync + 'static>(
+    State(state): State<AppState<S>>,
+) -> Result<Json<Vec<Value>>, ApiError> {
+    // Lists what identification can match, so expired and soft-deleted records are left out
+    let now = chrono::Utc::now();
+    let records = with_storage(&state, |storage| storage.get_embeddings_page(0, usize::MAX)).await?;
+    let records = records.into_iter().filter(|r| !r.is_expired_at(now) && !r.is_deleted());
+    Ok(Json(
+        records
+            .map(|r| json!({ "id": r.id, "name": r.name, "created_at": r.created_at }))
+            .collect(),
+    ))
+}
@@ -0,0 +1,55 @@
+use anyhow::Result;
+use ex04_storage_local_solution::TempFileGuard;
+use std::process::{Command, Output};
+
+const FIXTURES: &str = "../../../app/test_images";
+
+fn face_auth(db: &str, args: &[&str]) -> Result<Output> {
+    Ok(Command::new(env!("CARGO_BIN_EXE_face-auth")).args(["--db", db]).args(args).output()?)
+}
+
+fn fixture(name: &str) -> String {
+    format!("{FIXTURES}/{name}")
+}
+
+#[test]
+fn enroll_verify_identify_on_fixtures() -> Result<()> {
+    let db = format!("workshop_cli_{}.db", std::process::id());
+    let _guard = TempFileGuard { path: db.clone() };
+    let (brad1, brad2, tom) = (fixture("brad1.png"), fixture("brad2.png"), fixture("tom.png"));
+
+    let enrolled = face_auth(&db, &["enroll", "--name", "brad", "--image", &brad1])?;
+    assert!(enrolled.status.success(), "{}", String::from_utf8_lossy(&enrolled.stderr));
+    assert!(String::from_utf8(enrolled.stdout)?.starts_with("Enrolled brad as "));
+
+    let same = face_auth(&db, &["verify", "--image-a", &brad1, "--image-b", &brad2])?;
+    assert_eq!(same.status.code(), Some(0));
+    assert!(String::from_utf8(same.stdout)?.starts_with("Match"));
+    let different = face_auth(&db, &["--json", "verify", "--image-a", &brad1, "--image-b", &tom])?;
+    assert_eq!(different.status.code(), Some(1));
+    let decision: serde_json::Value = serde_json::from_slice(&different.stdout)?;
+    assert_eq!(decision["match"], false);
+
+    let known = face_auth(&db, &["identify", "--image", &brad2, "--json"])?;
+    assert_eq!(known.status.code(), Some(0));
+    let found: serde_json::Value = serde_json::from_slice(&known.stdout)?;
+    assert_eq!(found["name"], "brad");
+    let unknown = face_auth(&db, &["identify", "--image", &tom])?;
+    assert_eq!(unknown.status.code(), Some(1));
+    assert_eq!(String::from_utf8(unknown.stdout)?.trim(), "Unknown face");
+    Ok(())
+}
+
+#[test]
+fn bad_input_exits_with_an_error() -> Result<()> {
+    let db = format!("workshop_cli_missing_{}.db", std::process::id());
+    let _guard = TempFileGuard { path: db.clone() };
+
+    let missing = face_auth(&db, &["identify", "--image", "no_such_photo.png"])?;
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(String::from_utf8(missing.stderr)?.contains("no_such_photo.png"));
+
+    let usage = face_auth(&db, &["enroll", "--image", &fixture("brad1.png")])?;
+    assert_eq!(usage.status.code(), Some(2), "Missing --name should be a usage error");
+    Ok(())
+}
@@ -0,0 +1,123 @@
+#![cfg(feature = "server")]
+
+use anyhow::Result;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use candle_nn::Func;
+use ex02_embeddings_solution::ModelPool;
+use ex04_storage_local_solution::{EmbeddingStorage, InMemoryStorage, SqliteStorage, TempFileGuard};
+use ex05_retrieval_solution::add_record;
+use face_auth_cli::server::{router, AppState};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+const BOUNDARY: &str = "face-auth-test-boundary";
+
+// Flattened pixels stand in for the embedding model, so no weights are downloaded:
+// the same photo still matches itself exactly
+fn flatten_model() -> ModelPool {
+    ModelPool::from_models(vec![Func::new(|xs| xs.flatten_from(1))]).expect("one model is enough for a pool")
+}
+
+fn state() -> AppState<InMemoryStorage> {
+    AppState::new(flatten_model(), InMemoryStorage::new(), 0.99)
+}
+
+fn multipart(uri: &str, name: Option<&str>, image: &[u8]) -> Result<Request<Body>> {
+    let mut body = Vec::new();
+    if let Some(name) = name {
+        body.extend(format!("--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\n{name}\r\n").as_bytes());
+    }
+    body.extend(
+        format!("--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"face.png\"\r\nContent-Type: image/png\r\n\r\n")
+            .as_bytes(),
+    );
+    body.extend(image);
+    body.extend(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    Ok(Request::post(uri)
+        .header("content-type", format!("multipart/form-data; boundary={BOUNDARY}"))
+        .body(Body::from(body))?)
+}
+
+async fn send(app: &axum::Router, request: Request<Body>) -> Result<(StatusCode, serde_json::Value)> {
+    let response = app.clone().oneshot(request).await?;
+    let status = response.status();
+    let bytes = response.into_body().collect().await?.to_bytes();
+    Ok((status, serde_json::from_slice(&bytes)?))
+}
+
+#[tokio::test]
+async fn enrolled_fixture_is_identified() -> Result<()> {
+    let app = router(state());
+    let brad = std::fs::read("../../../app/test_images/brad1.png")?;
+    let tom = std::fs::read("../../../app/test_images/tom.png")?;
+
+    let (status, enrolled) = send(&app, multipart("/enroll", Some("brad"), &brad)?).await?;
+    assert_eq!(status, StatusCode::CREATED, "{enrolled}");
+    assert_eq!(enrolled["name"], "brad");
+
+    let (status, found) = send(&app, multipart("/identify", None, &brad)?).await?;
+    assert_eq!(status, StatusCode::OK, "{found}");
+    assert_eq!((&found["id"], &found["name"]), (&enrolled["id"], &enrolled["name"]));
+    assert!(found["similarity"].as_f64().unwrap() > 0.99);
+
+    let (status, _) = send(&app, multipart("/identify", None, &tom)?).await?;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, gallery) = send(&app, Request::get("/gallery").body(Body::empty())?).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(gallery.as_array().map(Vec::len), Some(1));
+    assert_eq!(gallery[0]["id"], enrolled["id"]);
+    assert!(gallery[0].get("embedding").is_none());
+    Ok(())
+}
+
+// `face-auth serve` shares one SQLite connection between all request threads
+#[tokio::test]
+async fn sqlite_gallery_serves_enroll_and_identify() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("face_auth_server_{}.db", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let _guard = TempFileGuard { path: path.clone() };
+    let storage = SqliteStorage::new(&path)?;
+    let app = router(AppState::new(flatten_model(), storage, 0.99));
+    let brad = std::fs::read("../../../app/test_images/brad1.png")?;
+
+    let (status, enrolled) = send(&app, multipart("/enroll", Some("brad"), &brad)?).await?;
+    assert_eq!(status, StatusCode::CREATED, "{enrolled}");
+    let (status, found) = send(&app, multipart("/identify", None, &brad)?).await?;
+    assert_eq!(status, StatusCode::OK, "{found}");
+    assert_eq!(found["id"], enrolled["id"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn gallery_lists_only_live_records() -> Result<()> {
+    let mut storage = InMemoryStorage::new();
+    let live = add_record(&mut storage, "alice", vec![1.0, 0.0])?;
+    let deleted = add_record(&mut storage, "bob", vec![0.0, 1.0])?;
+    storage.soft_delete(&deleted)?;
+    let expired = add_record(&mut storage, "carol", vec![0.7, 0.7])?;
+    let mut record = storage.get_embedding(&expired)?.expect("carol was just stored");
+    record.expires_at = Some(record.created_at);
+    storage.update_embedding(record)?;
+    let app = router(AppState::new(flatten_model(), storage, 0.99));
+
+    let (status, gallery) = send(&app, Request::get("/gallery").body(Body::empty())?).await?;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(gallery.as_array().map(Vec::len), Some(1));
+    assert_eq!(gallery[0]["id"], live.as_str());
+    Ok(())
+}
+
+#[tokio::test]
+async fn bad_uploads_are_rejected() -> Result<()> {
+    let app = router(state());
+    let (status, body) = send(&app, multipart("/identify", None, b"definitely not a png")?).await?;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+
+    let brad = std::fs::read("../../../app/test_images/brad1.png")?;
+    let (status, body) = send(&app, multipart("/enroll", None, &brad)?).await?;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("name"));
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use thiserror::Error;
+
+/// Boxed underlying cause carried by the error variants.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Typed failure modes shared by the solution crates, so callers can match on what went wrong.
+///
+/// The core pipeline returns it directly: image loading (`load_image*`, `detect_and_crop`), model
+/// loading and inference (`build_model*`, `compute_embedding(s)`, `ModelPool`, `OnnxModel`), and
+/// the similarity and verification functions. The helpers built on top of them (augmentation,
+/// quality scoring, calibration, evaluation, storage backends and retrieval) return
+/// `anyhow::Result` on purpose; they wrap these, recoverable with `err.downcast_ref::<FaceAuthError>()`.
+#[derive(Debug, Error)]
+pub enum FaceAuthError {
+    #[error("Failed to load image '{path}': {source}")]
+    ImageLoad {
+        path: String,
+        #[source]
+        source: BoxError,
+    },
+    #[error("Failed to load model: {0}")]
+    ModelLoad(#[source] BoxError),
+    #[error("Dimension mismatch: expected {expected}, got {actual}")]
+    DimensionMismatch { expected: usize, actual: usize },
+    /// An embedding with no values was passed where one is compared or scored.
+    #[error("Embeddings must not be empty")]
+    EmptyInput,
+    #[error("Storage backend error: {0}")]
+    StorageError(#[source] BoxError),
+    #[error("No embedding with id '{0}'")]
+    NotFound(String),
+    /// The caller keyed by this identity or address has used up its attempts for now.
+    #[error("Too many attempts for '{0}', try again later")]
+    RateLimited(String),
+    /// Anything not covered above; lets typed functions use `?` on `anyhow` results.
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl FaceAuthError {
+    pub fn image_load(path: impl Into<String>, source: impl Into<BoxError>) -> Self {
+        FaceAuthError::ImageLoad {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+}